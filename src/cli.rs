@@ -23,6 +23,15 @@ pub(crate) enum Command {
         #[command(subcommand)]
         sub: OpCommand
     },
+    Focus {
+        #[command(subcommand)]
+        sub: FocusCommand
+    },
+    Query {
+        #[command(subcommand)]
+        sub: QueryCommand
+    },
+    Dmenu,
     #[cfg(feature="debug")]
     Debug
 }
@@ -40,7 +49,11 @@ pub(crate) enum DaemonCommand {
 pub(crate) enum LaunchCommand {
     App,
     // Window,
-    WindowInApp
+    WindowInApp,
+    Launcher,
+    Provider {
+        mode: String
+    }
 }
 
 #[derive(Clone, Debug, Subcommand)]
@@ -50,13 +63,57 @@ pub(crate) enum OpCommand {
         direction: Direction
     },
     ShowWindows,
-    SelectCurrent,
+    SelectCurrent {
+        #[arg(short, long)]
+        bring: bool
+    },
+    SelectIndex {
+        index: usize,
+        #[arg(short, long)]
+        bring: bool
+    },
+    Filter {
+        text: String
+    },
+    Action {
+        #[command(subcommand)]
+        action: WindowAction
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum WindowAction {
+    Close,
+    CloseApp,
+    ToggleFullscreen,
+    ToggleFloating,
+    Pin,
+    MoveHere
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum FocusCommand {
+    Urgent
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum QueryCommand {
+    Windows {
+        #[arg(long)]
+        show_filtered: bool
+    }
 }
 
 #[derive(Clone, Debug, Subcommand)]
 pub(crate) enum Direction {
     Prev,
-    Next
+    Next,
+    Up,
+    Down,
+    First,
+    Last,
+    PageUp,
+    PageDown
 }
 
 pub(crate) trait CliHandler: Sized {
@@ -72,7 +129,19 @@ pub(crate) trait CliHandler: Sized {
 
     fn show_windows() -> Self::Output;
 
-    fn select_current() -> Self::Output;
+    fn select_current(bring: bool) -> Self::Output;
+
+    fn select_index(index: usize, bring: bool) -> Self::Output;
+
+    fn filter(text: String) -> Self::Output;
+
+    fn window_action(action: WindowAction) -> Self::Output;
+
+    fn focus_urgent() -> Self::Output;
+
+    fn query_windows(show_filtered: bool) -> Self::Output;
+
+    fn dmenu() -> Self::Output;
 }
 
 pub(crate) fn run_cli<T: CliHandler>() -> Result<T::Output> {
@@ -89,8 +158,18 @@ pub(crate) fn run_cli<T: CliHandler>() -> Result<T::Output> {
         Command::Operate { sub } => match sub {
             OpCommand::MoveCursor { direction } => T::move_cursor(direction),
             OpCommand::ShowWindows => T::show_windows(),
-            OpCommand::SelectCurrent => T::select_current(),
+            OpCommand::SelectCurrent { bring } => T::select_current(bring),
+            OpCommand::SelectIndex { index, bring } => T::select_index(index, bring),
+            OpCommand::Filter { text } => T::filter(text),
+            OpCommand::Action { action } => T::window_action(action),
+        },
+        Command::Focus { sub } => match sub {
+            FocusCommand::Urgent => T::focus_urgent(),
+        },
+        Command::Query { sub } => match sub {
+            QueryCommand::Windows { show_filtered } => T::query_windows(show_filtered),
         },
+        Command::Dmenu => T::dmenu(),
         #[cfg(feature="debug")]
         Command::Debug { sub } => todo!(),
     };
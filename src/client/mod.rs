@@ -1,21 +1,14 @@
-use std::{io::{Read as _, Write as _}, os::unix::net::UnixStream};
+use std::{io::{BufRead, Read as _, Write as _}, net::Shutdown, os::unix::net::UnixStream};
 
 use anyhow::{bail, Context as _, Result};
 use log::debug;
-use crate::protocol::{decode_response, encode_request, get_socket_path, Request, Response};
+use crate::protocol::{decode_response, encode_request, get_socket_path, DmenuItem, Request, Response};
 
 mod unix;
 
 
 pub(crate) fn send_request(req: Request) -> Result<()> {
-    let mut stream = UnixStream::connect(get_socket_path())
-        .context("Failed to connect the daemon")?;
-    debug!("Unixstream was created");
-    let encode_request = encode_request(req);
-    stream.write_all(&encode_request)?;
-    let mut buf = vec![0; 1024];
-    let size = stream.read(&mut buf)?;
-    decode_response(&buf[..size])
+    query(req)
         .and_then(|it|
             if it == Response::Accepted {
                 Ok(())
@@ -24,3 +17,39 @@ pub(crate) fn send_request(req: Request) -> Result<()> {
             }
         )
 }
+
+pub(crate) fn query(req: Request) -> Result<Response> {
+    let mut stream = UnixStream::connect(get_socket_path())
+        .context("Failed to connect the daemon")?;
+    debug!("Unixstream was created");
+    let encode_request = encode_request(req);
+    stream.write_all(&encode_request)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf)?;
+    decode_response(&buf)
+}
+
+pub(crate) fn read_dmenu_items(reader: impl BufRead) -> Result<Vec<DmenuItem>> {
+    reader.lines()
+        .map(|line| line.map(|it| parse_dmenu_item(&it)).context("Failed to read the items"))
+        .filter(|it| it.as_ref().map_or(true, |it| !it.text.is_empty()))
+        .collect()
+}
+
+// Accepts rofi's extended format: `text\0icon\x1fname`
+fn parse_dmenu_item(line: &str) -> DmenuItem {
+    let (text, options) = line.split_once('\0').unwrap_or((line, ""));
+    let mut options = options.split('\x1f');
+    let mut icon = None;
+    while let Some(key) = options.next() {
+        let value = options.next();
+        if key == "icon" {
+            icon = value.filter(|it| !it.is_empty()).map(str::to_string);
+        }
+    }
+    DmenuItem {
+        text: text.to_string(),
+        icon
+    }
+}
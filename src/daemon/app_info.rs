@@ -0,0 +1,88 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use log::debug;
+
+use super::{desktop::{DesktopEntry, DesktopIndex}, icon::{lookup_app_icon, lookup_fallback_icon, IconParams}};
+
+#[derive(Clone, Debug)]
+pub(crate) struct AppInfo {
+    name: String,
+    icon: Option<PathBuf>
+}
+
+#[derive(Debug)]
+pub(crate) struct AppInfoCache {
+    desktop_index: DesktopIndex,
+    icon_params: IconParams,
+    apps: HashMap<String, AppInfo>
+}
+
+impl AppInfoCache {
+    pub(crate) fn new(icon_size: u16) -> Self {
+        Self {
+            desktop_index: DesktopIndex::load(),
+            icon_params: IconParams::new(icon_size),
+            apps: HashMap::new()
+        }
+    }
+
+    pub(crate) const fn icon_params(&self) -> &IconParams {
+        &self.icon_params
+    }
+
+    pub(crate) fn set_icon_theme(&mut self, theme: Option<String>) {
+        if self.icon_params.theme != theme {
+            debug!("Icon theme was changed: {theme:?}");
+            self.icon_params.theme = theme;
+            self.apps.clear();
+        }
+    }
+
+    pub(crate) fn set_icon_scale(&mut self, scale: u16) {
+        if self.icon_params.scale != scale {
+            debug!("Icon scale was changed: {scale}");
+            self.icon_params.scale = scale;
+            self.apps.clear();
+        }
+    }
+
+    pub(crate) fn refresh(&mut self) {
+        if self.desktop_index.refresh() {
+            self.apps.clear();
+        }
+    }
+
+    pub(crate) fn launchable(&self) -> Vec<DesktopEntry> {
+        self.desktop_index.launchable().cloned().collect()
+    }
+
+    pub(crate) fn find_entry(&self, app_id: &str) -> Option<&DesktopEntry> {
+        self.desktop_index.find(app_id)
+    }
+
+    pub(crate) fn get(&mut self, app_id: &str) -> &AppInfo {
+        let desktop_index = &self.desktop_index;
+        let icon_params = &self.icon_params;
+        self.apps.entry(app_id.to_string())
+            .or_insert_with(|| {
+                let entry = desktop_index.find(app_id);
+                let icon = lookup_app_icon(app_id, entry, icon_params)
+                    .or_else(|| lookup_fallback_icon(icon_params));
+                debug!("Resolved the icon of {app_id}: {icon:?}");
+                AppInfo {
+                    name: entry.map_or(app_id, |it| it.name()).to_string(),
+                    icon
+                }
+            })
+    }
+}
+
+impl AppInfo {
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn icon(&self) -> Option<&PathBuf> {
+        self.icon.as_ref()
+    }
+}
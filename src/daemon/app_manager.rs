@@ -1,67 +1,179 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::{Duration, Instant}};
 
 use hyprland::shared::Address;
 
+use super::{config::Config, rules::{find_ignore_rule, resolve_app_id, GroupRule, IgnoreRule}};
+
+// How long focus changes left over from peeking are ignored after it ended
+const SETTLE_TIMEOUT: Duration = Duration::from_millis(500);
+
 pub(crate) type Applications = Vec<(String, Vec<Window>)>;
 
 #[derive(Clone, Debug)]
 pub(crate) struct AppManager {
     windows: HashMap<Address, String>,
-    apps: Applications
+    apps: Applications,
+    filtered: HashMap<Address, FilteredWindow>,
+    urgent: Vec<Address>,
+    peeking: bool,
+    // The window focused after peeking, with when to stop waiting for its event
+    settle: Option<(Address, Instant)>,
+    ignore_rules: Vec<IgnoreRule>,
+    group_rules: Vec<GroupRule>
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct Window {
+    class: String,
     title: String,
-    addr: Address
+    addr: Address,
+    workspace: String,
+    state: WindowState,
+    // Members of the Hyprland group in the order of the tabs, empty unless grouped
+    group: Vec<Address>
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct WindowState {
+    pub(crate) floating: bool,
+    pub(crate) fullscreen: bool,
+    pub(crate) pinned: bool,
+    pub(crate) urgent: bool,
+    pub(crate) xwayland: bool
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct FilteredWindow {
+    window: Window,
+    rule: usize
 }
 
 impl AppManager {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(config: &Config) -> Self {
         Self {
             windows: HashMap::new(),
-            apps: Vec::new()
+            apps: Vec::new(),
+            filtered: HashMap::new(),
+            urgent: Vec::new(),
+            peeking: false,
+            settle: None,
+            ignore_rules: config.ignore.clone(),
+            group_rules: config.group.clone()
         }
     }
 
-    pub(super) fn add_window(&mut self, class: String, addr: Address, title: String) -> Option<()> {
-        self.windows.insert(addr.clone(), class.clone());
+    pub(super) fn add_window(&mut self, window: Window) -> Option<()> {
+        if let Some(rule) = find_ignore_rule(&self.ignore_rules, &window) {
+            self.filtered.insert(window.addr.clone(), FilteredWindow { window, rule });
+            return Some(())
+        }
+
+        let app_id = self.app_id(&window);
+        self.windows.insert(window.addr.clone(), app_id.clone());
 
-        let pos = self.app_pos(&class);
+        let pos = self.app_pos(&app_id);
         if let Some(i) = pos {
             let (_, vec) = self.apps.get_mut(i)?;
-            vec.push(Window::new(addr, title));
+            vec.push(window);
         } else {
-            self.apps.push((class, vec![Window::new(addr, title)]));
+            self.apps.push((app_id, vec![window]));
         }
 
         Some(())
     }
 
+    pub(super) fn contains(&self, addr: &Address) -> bool {
+        self.windows.contains_key(addr)
+    }
+
     pub(super) fn remove_window(&mut self, addr: &Address) -> Option<()> {
-        let class = self.windows.remove(&addr)?;
+        self.urgent.retain(|it| it != addr);
+        if self.filtered.remove(addr).is_some() {
+            return Some(())
+        }
+
+        self.take_window(addr).map(|_| ())
+    }
 
-        let app_pos = self.app_pos(&class)?;
+    pub(super) fn update_window(&mut self, addr: &Address, f: impl FnOnce(&mut Window)) -> Option<()> {
+        if let Some(FilteredWindow { mut window, .. }) = self.filtered.remove(addr) {
+            f(&mut window);
+            return self.add_window(window)
+        }
+
+        let app_id = self.windows.get(addr)?.clone();
+        let app_pos = self.app_pos(&app_id)?;
         let (_, vec) = self.apps.get_mut(app_pos)?;
         let pos = Self::window_pos(vec, addr)?;
-        vec.remove(pos);
-        if vec.len() == 0 {
-            self.apps.remove(app_pos);
+        f(&mut vec[pos]);
+
+        let window = &vec[pos];
+        if find_ignore_rule(&self.ignore_rules, window).is_some()
+            || resolve_app_id(&self.group_rules, &window.class, &window.title) != app_id {
+            let window = self.take_window(addr)?;
+            return self.add_window(window)
         }
 
         Some(())
     }
 
-    pub(super) fn move_to_top(&mut self, class: String, addr: Address) -> Option<()> {
-        let app_pos = self.app_pos(&class)?;
+    pub(super) fn mark_urgent(&mut self, addr: &Address) -> Option<()> {
+        self.update_window(addr, |it| it.set_urgent(true))?;
+        // Ignored windows cannot be focused from the switcher either
+        if !self.contains(addr) {
+            return None
+        }
+        self.urgent.retain(|it| it != addr);
+        self.urgent.push(addr.clone());
+
+        Some(())
+    }
+
+    pub(super) fn take_urgent(&mut self) -> Option<Address> {
+        let addr = self.urgent.pop()?;
+        self.update_window(&addr, |it| it.set_urgent(false));
+
+        Some(addr)
+    }
+
+    pub(super) fn begin_peek(&mut self) {
+        self.peeking = true;
+        self.settle = None;
+    }
+
+    // The settled window is reordered right away, since Hyprland reports nothing when it is focused already
+    pub(super) fn end_peek(&mut self, settle: Option<Address>) {
+        self.peeking = false;
+        self.settle = None;
+        if let Some(addr) = settle {
+            self.move_to_top(&addr);
+            self.settle = Some((addr, Instant::now() + SETTLE_TIMEOUT));
+        }
+    }
+
+    pub(super) fn move_to_top(&mut self, addr: &Address) -> Option<()> {
+        // Focus changes caused by peeking must not reorder the windows, even when reported late
+        if self.peeking {
+            return None
+        }
+        if let Some((settle, until)) = self.settle.take() {
+            if &settle != addr && Instant::now() < until {
+                self.settle = Some((settle, until));
+                return None
+            }
+        }
+        self.urgent.retain(|it| it != addr);
+        let app_id = self.windows.get(addr)?;
+        let app_pos = self.app_pos(app_id)?;
 
         let mut app = self.apps.remove(app_pos);
 
         let (_, app_windows) = &mut app;
-        let window_pos = Self::window_pos(app_windows, &addr)?;
-        let window = app_windows.remove(window_pos);
+        let window_pos = Self::window_pos(app_windows, addr)?;
+        let mut window = app_windows.remove(window_pos);
+        window.state.urgent = false;
         app_windows.insert(0, window);
-        
+
         self.apps.insert(0, app);
 
         Some(())
@@ -71,8 +183,34 @@ impl AppManager {
         &self.apps
     }
 
-    fn app_pos(&self, class: &str) -> Option<usize> {
-        self.apps.iter().position(|(it, _)| class == it)
+    pub(crate) fn get_filtered(&self) -> impl Iterator<Item = &FilteredWindow> {
+        self.filtered.values()
+    }
+
+    pub(crate) fn describe_rule(&self, filtered: &FilteredWindow) -> String {
+        self.ignore_rules[filtered.rule].describe(filtered.rule)
+    }
+
+    pub(crate) fn app_id(&self, window: &Window) -> String {
+        resolve_app_id(&self.group_rules, &window.class, &window.title)
+    }
+
+    fn take_window(&mut self, addr: &Address) -> Option<Window> {
+        let app_id = self.windows.remove(addr)?;
+
+        let app_pos = self.app_pos(&app_id)?;
+        let (_, vec) = self.apps.get_mut(app_pos)?;
+        let pos = Self::window_pos(vec, addr)?;
+        let window = vec.remove(pos);
+        if vec.len() == 0 {
+            self.apps.remove(app_pos);
+        }
+
+        Some(window)
+    }
+
+    fn app_pos(&self, app_id: &str) -> Option<usize> {
+        self.apps.iter().position(|(it, _)| app_id == it)
     }
 
     fn window_pos(clients: &Vec<Window>, addr: &Address) -> Option<usize> {
@@ -80,16 +218,165 @@ impl AppManager {
     }
 }
 
+impl WindowState {
+    pub(crate) const fn merge(self, other: Self) -> Self {
+        Self {
+            floating: self.floating || other.floating,
+            fullscreen: self.fullscreen || other.fullscreen,
+            pinned: self.pinned || other.pinned,
+            urgent: self.urgent || other.urgent,
+            xwayland: self.xwayland || other.xwayland
+        }
+    }
+}
+
 impl Window {
-    pub(crate) const fn new(addr: Address, title: String) -> Self {
-        Self { title, addr }
+    pub(crate) const fn new(addr: Address, class: String, title: String, workspace: String, state: WindowState) -> Self {
+        Self { class, title, addr, workspace, state, group: Vec::new() }
     }
 
     pub(crate) const fn addr(&self) -> &Address {
         &self.addr
     }
 
+    pub(crate) fn class(&self) -> &str {
+        self.class.as_str()
+    }
+
     pub(crate) fn title(&self) -> &str {
         self.title.as_str()
     }
+
+    pub(crate) fn workspace(&self) -> &str {
+        self.workspace.as_str()
+    }
+
+    pub(crate) const fn state(&self) -> WindowState {
+        self.state
+    }
+
+    pub(crate) fn group(&self) -> &[Address] {
+        self.group.as_slice()
+    }
+
+    pub(crate) const fn floating(&self) -> bool {
+        self.state.floating
+    }
+
+    pub(crate) const fn xwayland(&self) -> bool {
+        self.state.xwayland
+    }
+
+    pub(crate) fn set_title(&mut self, title: String) {
+        self.title = title
+    }
+
+    pub(crate) fn set_workspace(&mut self, workspace: String) {
+        self.workspace = workspace
+    }
+
+    pub(crate) fn set_floating(&mut self, floating: bool) {
+        self.state.floating = floating
+    }
+
+    pub(crate) fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.state.fullscreen = fullscreen
+    }
+
+    pub(crate) fn set_pinned(&mut self, pinned: bool) {
+        self.state.pinned = pinned
+    }
+
+    pub(crate) fn set_urgent(&mut self, urgent: bool) {
+        self.state.urgent = urgent
+    }
+
+    pub(crate) fn set_group(&mut self, group: Vec<Address>) {
+        self.group = group
+    }
+}
+
+impl FilteredWindow {
+    pub(crate) const fn window(&self) -> &Window {
+        &self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyprland::shared::Address;
+
+    use crate::daemon::config::Config;
+
+    use super::{AppManager, Window, WindowState};
+
+    fn manager(classes: &[&str]) -> AppManager {
+        manager_with(&Config::default(), classes)
+    }
+
+    fn manager_with(config: &Config, classes: &[&str]) -> AppManager {
+        let mut app_manager = AppManager::new(config);
+        for class in classes {
+            let window = Window::new(Address::new(class), class.to_string(), String::new(), "1".to_string(), WindowState::default());
+            app_manager.add_window(window);
+        }
+        app_manager
+    }
+
+    fn order(app_manager: &AppManager) -> Vec<&str> {
+        app_manager.get_apps().iter().map(|(app_id, _)| app_id.as_str()).collect()
+    }
+
+    #[test]
+    fn peeking_keeps_the_order() {
+        let mut app_manager = manager(&["a", "b", "c"]);
+        app_manager.begin_peek();
+        app_manager.move_to_top(&Address::new("b"));
+        app_manager.move_to_top(&Address::new("c"));
+        assert_eq!(order(&app_manager), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn end_peek_settles_without_an_event() {
+        let mut app_manager = manager(&["a", "b", "c"]);
+        app_manager.begin_peek();
+        app_manager.move_to_top(&Address::new("c"));
+        app_manager.end_peek(Some(Address::new("c")));
+        assert_eq!(order(&app_manager), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn late_peek_events_are_ignored() {
+        let mut app_manager = manager(&["a", "b", "c"]);
+        app_manager.begin_peek();
+        app_manager.end_peek(Some(Address::new("c")));
+        app_manager.move_to_top(&Address::new("b"));
+        app_manager.move_to_top(&Address::new("c"));
+        assert_eq!(order(&app_manager), ["c", "a", "b"]);
+        // Once the settled window is reported, focus changes count again
+        app_manager.move_to_top(&Address::new("a"));
+        assert_eq!(order(&app_manager), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn ignored_windows_are_not_urgent() {
+        let config = toml::from_str(r#"
+            [[ignore]]
+            class = "scratch"
+        "#).unwrap();
+        let mut app_manager = manager_with(&config, &["a", "scratch"]);
+        app_manager.mark_urgent(&Address::new("scratch"));
+        app_manager.mark_urgent(&Address::new("a"));
+        assert_eq!(app_manager.take_urgent(), Some(Address::new("a")));
+        assert_eq!(app_manager.take_urgent(), None);
+    }
+
+    #[test]
+    fn end_peek_without_settling_unfreezes() {
+        let mut app_manager = manager(&["a", "b"]);
+        app_manager.begin_peek();
+        app_manager.end_peek(None);
+        app_manager.move_to_top(&Address::new("b"));
+        assert_eq!(order(&app_manager), ["b", "a"]);
+    }
 }
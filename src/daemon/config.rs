@@ -0,0 +1,191 @@
+use std::{env, fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context as _, Result};
+use log::debug;
+use serde::Deserialize;
+
+use crate::protocol::WindowAction;
+
+use super::{provider::ProviderConfig, rules::{deserialize_ignore_rules, GroupRule, IgnoreRule}};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) icon_size: u16,
+    pub(crate) urgent_first: bool,
+    pub(crate) start_on_previous: bool,
+    pub(crate) show_delay: u64,
+    // The only way to change the fade, as the transition on `.switcher` overrides any set in the user's CSS
+    pub(crate) fade_duration: u64,
+    pub(crate) peek: bool,
+    pub(crate) leave_fullscreen: bool,
+    pub(crate) layout: Layout,
+    pub(crate) layer_shell: LayerShellConfig,
+    pub(crate) keys: KeyConfig,
+    pub(crate) snapshot: SnapshotConfig,
+    #[serde(deserialize_with = "deserialize_ignore_rules")]
+    pub(crate) ignore: Vec<IgnoreRule>,
+    pub(crate) group: Vec<GroupRule>,
+    pub(crate) provider: Vec<ProviderConfig>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct SnapshotConfig {
+    pub(crate) enabled: bool,
+    pub(crate) width: u16,
+    pub(crate) delay: u64
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Layout {
+    #[default]
+    Horizontal,
+    List,
+    Grid
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct LayerShellConfig {
+    pub(crate) layer: ShellLayer,
+    pub(crate) anchor: Anchor,
+    pub(crate) margin: Margins,
+    pub(crate) exclusive_zone: i32,
+    pub(crate) keyboard: Keyboard
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ShellLayer {
+    Background,
+    Bottom,
+    Top,
+    #[default]
+    Overlay
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Anchor {
+    #[default]
+    Center,
+    Top,
+    Bottom
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Margins {
+    pub(crate) top: i32,
+    pub(crate) bottom: i32,
+    pub(crate) left: i32,
+    pub(crate) right: i32
+}
+
+// Hyprland only focuses an on-demand surface once it is clicked, so typing needs an exclusive grab
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Keyboard {
+    None,
+    #[default]
+    Exclusive,
+    OnDemand
+}
+
+// Accelerators in the GTK format (e.g. "<Control>w"); an empty string disables the key
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct KeyConfig {
+    pub(crate) close: String,
+    pub(crate) close_app: String,
+    pub(crate) toggle_fullscreen: String,
+    pub(crate) toggle_floating: String,
+    pub(crate) pin: String,
+    pub(crate) move_here: String
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            icon_size: 96,
+            urgent_first: false,
+            start_on_previous: true,
+            show_delay: 150,
+            fade_duration: 120,
+            peek: false,
+            leave_fullscreen: true,
+            layout: Layout::default(),
+            layer_shell: LayerShellConfig::default(),
+            keys: KeyConfig::default(),
+            snapshot: SnapshotConfig::default(),
+            ignore: Vec::new(),
+            group: Vec::new(),
+            provider: Vec::new()
+        }
+    }
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            width: 320,
+            delay: 300
+        }
+    }
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        Self {
+            close: "<Control>w".to_string(),
+            close_app: "<Control><Shift>w".to_string(),
+            toggle_fullscreen: "<Control>f".to_string(),
+            toggle_floating: "<Control>t".to_string(),
+            pin: "<Control>p".to_string(),
+            move_here: "<Control>m".to_string()
+        }
+    }
+}
+
+impl KeyConfig {
+    pub(crate) fn bindings(&self) -> [(WindowAction, &str); 6] {
+        [
+            (WindowAction::Close, &self.close),
+            (WindowAction::CloseApp, &self.close_app),
+            (WindowAction::ToggleFullscreen, &self.toggle_fullscreen),
+            (WindowAction::ToggleFloating, &self.toggle_floating),
+            (WindowAction::Pin, &self.pin),
+            (WindowAction::MoveHere, &self.move_here)
+        ]
+    }
+}
+
+impl Config {
+    pub(crate) fn load() -> Result<Self> {
+        let path = get_config_path();
+        debug!("Loading the config from: {}", path.to_str().unwrap_or("Unknown"));
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).context("Failed to read the config")
+        };
+        toml::from_str(&raw).context("Failed to parse the config")
+    }
+}
+
+fn get_config_path() -> PathBuf {
+    let mut buf = if let Ok(config_path) = env::var("XDG_CONFIG_HOME") {
+        PathBuf::from(config_path)
+    } else if let Ok(home) = env::var("HOME") {
+        PathBuf::from(home).join(".config")
+    } else {
+        PathBuf::from("/etc/xdg")
+    };
+
+    buf.push("sagi");
+    buf.push("config.toml");
+    buf
+}
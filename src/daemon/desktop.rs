@@ -0,0 +1,277 @@
+use std::{collections::{HashMap, HashSet}, env, fs, path::{Path, PathBuf}, time::SystemTime};
+
+use log::debug;
+
+#[derive(Clone, Debug)]
+pub(crate) struct DesktopEntry {
+    id: String,
+    file: PathBuf,
+    name: String,
+    icon: Option<String>,
+    exec: Option<String>,
+    path: Option<String>,
+    terminal: bool,
+    no_display: bool,
+    startup_wm_class: Option<String>
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DesktopIndex {
+    entries: Vec<DesktopEntry>,
+    by_wm_class: HashMap<String, usize>,
+    by_id: HashMap<String, usize>,
+    by_exec: HashMap<String, usize>,
+    dir_stamps: Vec<(PathBuf, Option<SystemTime>)>
+}
+
+impl DesktopIndex {
+    pub(crate) fn load() -> Self {
+        let mut index = Self::default();
+        index.rebuild();
+        index
+    }
+
+    pub(crate) fn refresh(&mut self) -> bool {
+        let changed = self.dir_stamps.iter().any(|(dir, stamp)| &modified(dir) != stamp);
+        if changed {
+            debug!("Applications directories were changed, rebuilding the desktop entry index");
+            self.rebuild();
+        }
+        changed
+    }
+
+    pub(crate) fn find(&self, class: &str) -> Option<&DesktopEntry> {
+        let class = class.to_lowercase();
+        self.by_wm_class.get(&class)
+            .or_else(|| self.by_id.get(&class))
+            .or_else(|| self.by_exec.get(&class))
+            .map(|&it| &self.entries[it])
+    }
+
+    pub(crate) fn launchable(&self) -> impl Iterator<Item = &DesktopEntry> {
+        self.entries.iter().filter(|it| !it.no_display && it.exec.is_some())
+    }
+
+    fn rebuild(&mut self) {
+        let dirs = application_dirs();
+        self.entries.clear();
+        self.by_wm_class.clear();
+        self.by_id.clear();
+        self.by_exec.clear();
+        // Subdirectories are stamped too, since adding a file to one does not touch its parent
+        let mut watched = dirs.clone();
+
+        let locales = locale_keys();
+        let mut seen_ids = HashSet::new();
+        for dir in &dirs {
+            let mut files = Vec::new();
+            collect_desktop_files(dir, dir, &mut files, &mut watched);
+            for (id, path) in files {
+                if !seen_ids.insert(id.clone()) {
+                    continue;
+                }
+                let Some(entry) = parse_entry(id, path, &locales) else { continue };
+                self.insert(entry);
+            }
+        }
+        self.dir_stamps = watched.iter().map(|it| (it.clone(), modified(it))).collect();
+        debug!("Indexed {} desktop entries", self.entries.len());
+    }
+
+    fn insert(&mut self, entry: DesktopEntry) {
+        let i = self.entries.len();
+        if let Some(wm_class) = &entry.startup_wm_class {
+            self.by_wm_class.entry(wm_class.to_lowercase()).or_insert(i);
+        }
+        let stem = entry.id.trim_end_matches(".desktop").to_lowercase();
+        if let Some((_, last)) = stem.rsplit_once('.') {
+            self.by_id.entry(last.to_string()).or_insert(i);
+        }
+        self.by_id.insert(stem, i);
+        if let Some(exec) = entry.exec.as_deref().and_then(exec_basename) {
+            self.by_exec.entry(exec.to_lowercase()).or_insert(i);
+        }
+        self.entries.push(entry);
+    }
+}
+
+impl DesktopEntry {
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub(crate) fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    pub(crate) fn app_id(&self) -> &str {
+        self.id.trim_end_matches(".desktop")
+    }
+
+    pub(crate) const fn file(&self) -> &PathBuf {
+        &self.file
+    }
+
+    pub(crate) fn exec(&self) -> Option<&str> {
+        self.exec.as_deref()
+    }
+
+    pub(crate) fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub(crate) const fn terminal(&self) -> bool {
+        self.terminal
+    }
+}
+
+fn application_dirs() -> Vec<PathBuf> {
+    let data_home = if let Ok(data_home) = env::var("XDG_DATA_HOME") {
+        Some(PathBuf::from(data_home))
+    } else {
+        env::var("HOME").ok().map(|it| PathBuf::from(it).join(".local/share"))
+    };
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|it| !it.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    data_home.into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|it| it.join("applications"))
+        .collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|it| it.modified()).ok()
+}
+
+fn collect_desktop_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>, subdirs: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else { return };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_desktop_files(root, &path, files, subdirs);
+            subdirs.push(path);
+        } else if path.extension().is_some_and(|it| it == "desktop") {
+            let Ok(relative) = path.strip_prefix(root) else { continue };
+            let id = relative.to_string_lossy().replace('/', "-");
+            files.push((id, path));
+        }
+    }
+}
+
+fn parse_entry(id: String, file: PathBuf, locales: &[String]) -> Option<DesktopEntry> {
+    let raw = fs::read_to_string(&file).ok()?;
+    let mut in_main_group = false;
+    let mut values = HashMap::new();
+    for line in raw.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_main_group = line == "[Desktop Entry]";
+        } else if in_main_group && !line.starts_with('#') {
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim(), unescape(value.trim()));
+            }
+        }
+    }
+
+    let get = |key: &str| values.get(key).map(String::as_str);
+    if get("Type") != Some("Application") || get("Hidden") == Some("true") {
+        return None
+    }
+
+    let name = locales.iter()
+        .find_map(|it| get(&format!("Name[{it}]")))
+        .or_else(|| get("Name"))?
+        .to_string();
+
+    Some(DesktopEntry {
+        id,
+        file,
+        name,
+        icon: get("Icon").map(str::to_string),
+        exec: get("Exec").map(str::to_string),
+        path: get("Path").filter(|it| !it.is_empty()).map(str::to_string),
+        terminal: get("Terminal") == Some("true"),
+        no_display: get("NoDisplay") == Some("true"),
+        startup_wm_class: get("StartupWMClass").map(str::to_string)
+    })
+}
+
+// The escapes of string values, which come before any quoting rules of the key itself
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\')
+        }
+    }
+    unescaped
+}
+
+fn exec_basename(exec: &str) -> Option<&str> {
+    let program = exec.split_whitespace()
+        .find(|it| *it != "env" && !it.contains('='))?
+        .trim_matches('"');
+    program.rsplit('/').next()
+}
+
+fn locale_keys() -> Vec<String> {
+    let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|it| env::var(it).ok())
+        .find(|it| !it.is_empty())
+    else {
+        return Vec::new()
+    };
+
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale.as_str(), None)
+    };
+    let locale = locale.split('.').next().unwrap_or(locale);
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None)
+    };
+
+    let mut keys = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        keys.push(format!("{lang}_{country}@{modifier}"));
+    }
+    if let Some(country) = country {
+        keys.push(format!("{lang}_{country}"));
+    }
+    if let Some(modifier) = modifier {
+        keys.push(format!("{lang}@{modifier}"));
+    }
+    keys.push(lang.to_string());
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unescape;
+
+    #[test]
+    fn unescapes_string_values() {
+        assert_eq!(unescape(r"a\sb\tc\nd"), "a b\tc\nd");
+        assert_eq!(unescape(r#"sh -c "echo \\$HOME""#), r#"sh -c "echo \$HOME""#);
+        // Other escapes are left for the key to interpret
+        assert_eq!(unescape(r#""say \"hi\"""#), r#""say \"hi\"""#);
+    }
+}
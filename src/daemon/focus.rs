@@ -0,0 +1,197 @@
+use hyprland::{data::{Client, FullscreenMode, Monitor}, dispatch::{Dispatch, DispatchType, FullscreenType, WindowIdentifier}, shared::Address};
+use log::debug;
+
+pub(super) trait Dispatcher {
+    fn dispatch(&mut self, command: DispatchType<'_>) -> hyprland::Result<()>;
+}
+
+pub(super) struct Hyprctl;
+
+impl Dispatcher for Hyprctl {
+    fn dispatch(&mut self, command: DispatchType<'_>) -> hyprland::Result<()> {
+        Dispatch::call(command)
+    }
+}
+
+// A bare FocusWindow doesn't reveal windows on hidden special workspaces, behind a fullscreen window or in the background of a group
+pub(super) fn focus_window(dispatcher: &mut impl Dispatcher, addr: &Address, clients: &[Client], monitors: &[Monitor], leave_fullscreen: bool) -> hyprland::Result<()> {
+    let Some(target) = clients.iter().find(|it| &it.address == addr) else {
+        debug!("Window {addr} is unknown to Hyprland, focusing it anyway");
+        return dispatcher.dispatch(DispatchType::FocusWindow(WindowIdentifier::Address(addr.clone())))
+    };
+
+    let workspace = &target.workspace;
+    if is_special(target) && !monitors.iter().any(|it| it.special_workspace.id == workspace.id) {
+        // The unnamed special workspace is just called "special"
+        let name = workspace.name.strip_prefix("special:").map(str::to_string);
+        dispatcher.dispatch(DispatchType::ToggleSpecialWorkspace(name))?;
+    }
+
+    let fullscreen = clients.iter()
+        .find(|it| it.workspace.id == workspace.id && it.fullscreen != FullscreenMode::None && !same_group(target, it));
+    if let (true, Some(fullscreen)) = (leave_fullscreen, fullscreen) {
+        dispatcher.dispatch(DispatchType::FocusWindow(WindowIdentifier::Address(fullscreen.address.clone())))?;
+        dispatcher.dispatch(DispatchType::ToggleFullscreen(fullscreen_type(fullscreen.fullscreen)))?;
+    }
+
+    if let Some(active) = active_group_member(target, clients).filter(|it| it.address != target.address) {
+        let index = target.grouped.iter()
+            .position(|it| **it == target.address)
+            .map_or(1, |it| it + 1);
+        dispatcher.dispatch(DispatchType::FocusWindow(WindowIdentifier::Address(active.address.clone())))?;
+        dispatcher.dispatch(DispatchType::Custom("changegroupactive", &index.to_string()))?;
+    }
+
+    dispatcher.dispatch(DispatchType::FocusWindow(WindowIdentifier::Address(target.address.clone())))
+}
+
+fn is_special(client: &Client) -> bool {
+    client.workspace.id < 0
+}
+
+fn same_group(target: &Client, other: &Client) -> bool {
+    target.address == other.address || target.grouped.iter().any(|it| **it == other.address)
+}
+
+// Only the active member of a group is shown, and it is always the one focused most recently
+fn active_group_member<'a>(target: &Client, clients: &'a [Client]) -> Option<&'a Client> {
+    if target.grouped.len() < 2 {
+        return None
+    }
+    clients.iter()
+        .filter(|it| it.focus_history_id >= 0 && same_group(target, it))
+        .min_by_key(|it| it.focus_history_id)
+}
+
+fn fullscreen_type(mode: FullscreenMode) -> FullscreenType {
+    match mode {
+        FullscreenMode::Maximized => FullscreenType::Maximize,
+        _ => FullscreenType::Real
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyprland::{data::{Client, FullscreenMode, Monitor, Transforms, WorkspaceBasic}, dispatch::DispatchType, shared::{Address, WorkspaceId}};
+
+    use super::{focus_window, Dispatcher};
+
+    // Records the commands in the form `hyprctl dispatch` takes them
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Dispatcher for Recorder {
+        fn dispatch(&mut self, command: DispatchType<'_>) -> hyprland::Result<()> {
+            self.0.push(match command {
+                DispatchType::FocusWindow(window) => format!("focuswindow {window}"),
+                DispatchType::ToggleSpecialWorkspace(name) => format!("togglespecialworkspace {}", name.unwrap_or_default()),
+                DispatchType::ToggleFullscreen(mode) => format!("fullscreen {mode}"),
+                DispatchType::Custom(name, args) => format!("{name} {args}"),
+                command => format!("{command:?}")
+            });
+            Ok(())
+        }
+    }
+
+    fn client(addr: &str, workspace: WorkspaceId, workspace_name: &str) -> Client {
+        Client {
+            address: Address::new(addr),
+            at: (0, 0),
+            size: (100, 100),
+            workspace: WorkspaceBasic { id: workspace, name: workspace_name.to_string() },
+            floating: false,
+            fullscreen: FullscreenMode::None,
+            fullscreen_client: FullscreenMode::None,
+            monitor: Some(0),
+            initial_class: "app".to_string(),
+            class: "app".to_string(),
+            initial_title: String::new(),
+            title: String::new(),
+            pid: 1,
+            xwayland: false,
+            pinned: false,
+            grouped: Vec::new(),
+            mapped: true,
+            swallowing: None,
+            focus_history_id: 0
+        }
+    }
+
+    fn monitor(special: WorkspaceId) -> Monitor {
+        Monitor {
+            id: 0,
+            name: "DP-1".to_string(),
+            description: String::new(),
+            width: 1920,
+            height: 1080,
+            refresh_rate: 60.0,
+            x: 0,
+            y: 0,
+            active_workspace: WorkspaceBasic { id: 1, name: "1".to_string() },
+            special_workspace: WorkspaceBasic { id: special, name: String::new() },
+            reserved: (0, 0, 0, 0),
+            scale: 1.0,
+            transform: Transforms::Normal,
+            focused: true,
+            dpms_status: true,
+            vrr: false,
+            disabled: false
+        }
+    }
+
+    fn group(clients: &mut [Client]) {
+        let members: Vec<_> = clients.iter().map(|it| Box::new(it.address.clone())).collect();
+        for client in clients {
+            client.grouped = members.clone();
+        }
+    }
+
+    fn focus(addr: &str, clients: &[Client], monitors: &[Monitor], leave_fullscreen: bool) -> Vec<String> {
+        let mut recorder = Recorder::default();
+        focus_window(&mut recorder, &Address::new(addr), clients, monitors, leave_fullscreen).unwrap();
+        recorder.0
+    }
+
+    #[test]
+    fn shows_hidden_special_workspace() {
+        let clients = [client("0x1", -98, "special:term")];
+        assert_eq!(focus("0x1", &clients, &[monitor(0)], false), [
+            "togglespecialworkspace term",
+            "focuswindow address:0x1"
+        ]);
+        // Toggling it again would hide it
+        assert_eq!(focus("0x1", &clients, &[monitor(-98)], false), ["focuswindow address:0x1"]);
+    }
+
+    #[test]
+    fn leaves_fullscreen_sibling_only_when_asked() {
+        let mut fullscreen = client("0x1", 1, "1");
+        fullscreen.fullscreen = FullscreenMode::Fullscreen;
+        let clients = [fullscreen, client("0x2", 1, "1")];
+        assert_eq!(focus("0x2", &clients, &[monitor(0)], true), [
+            "focuswindow address:0x1",
+            "fullscreen 0",
+            "focuswindow address:0x2"
+        ]);
+        assert_eq!(focus("0x2", &clients, &[monitor(0)], false), ["focuswindow address:0x2"]);
+    }
+
+    #[test]
+    fn switches_to_background_group_member() {
+        let mut clients = [client("0x1", 1, "1"), client("0x2", 1, "1"), client("0x3", 1, "1")];
+        clients[1].focus_history_id = 1;
+        clients[2].focus_history_id = 2;
+        group(&mut clients);
+        assert_eq!(focus("0x3", &clients, &[monitor(0)], false), [
+            "focuswindow address:0x1",
+            "changegroupactive 3",
+            "focuswindow address:0x3"
+        ]);
+        assert_eq!(focus("0x1", &clients, &[monitor(0)], false), ["focuswindow address:0x1"]);
+    }
+
+    #[test]
+    fn focuses_unknown_window_anyway() {
+        assert_eq!(focus("0x9", &[client("0x1", 1, "1")], &[monitor(0)], true), ["focuswindow address:0x9"]);
+    }
+}
@@ -1,5 +1,81 @@
 pub(super) const DEFAULT_CSS: &str = r#"
+.switcher {
+    opacity: 0;
+}
+
+.switcher.shown {
+    opacity: 1;
+}
+
 .app-frame.current-item {
     border-color: Red;
 }
+
+.tile-label {
+    font-size: small;
+}
+
+.switcher-caption {
+    font-size: large;
+    font-weight: bold;
+    margin: 8px;
+}
+
+.switcher-filter {
+    font-size: large;
+    margin: 8px;
+}
+
+.letter-avatar {
+    font-size: 48px;
+    font-weight: bold;
+    border-radius: 8px;
+    background-color: alpha(currentColor, 0.1);
+}
+
+.app-frame.urgent {
+    border-color: Orange;
+}
+
+.window-count {
+    font-size: small;
+    font-weight: bold;
+    min-width: 16px;
+    padding: 0 4px;
+    border-radius: 8px;
+    background-color: alpha(currentColor, 0.2);
+}
+
+.state-indicators .indicator {
+    font-size: x-small;
+    margin: 2px;
+}
+
+.state-indicators .urgent {
+    color: Orange;
+}
+
+.app-badge {
+    border-radius: 4px;
+}
+
+.group-tabs {
+    margin: 0 8px 4px 8px;
+}
+
+.group-tab {
+    font-size: x-small;
+    padding: 2px 4px;
+    border-radius: 4px;
+}
+
+.group-tab.active-tab {
+    background-color: alpha(currentColor, 0.2);
+}
 "#;
+
+// Loaded above the user's CSS, since closing the window waits for exactly this long.
+// GTK reports no end of a transition, so a `transition` set on `.switcher` elsewhere is never used.
+pub(super) fn fade_css(duration: u64) -> String {
+    format!(".switcher {{ transition: opacity {duration}ms ease-out; }}")
+}
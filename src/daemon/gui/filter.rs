@@ -0,0 +1,68 @@
+use gtk::glib::markup_escape_text;
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 12;
+const BOUNDARY_BONUS: i64 = 8;
+
+#[derive(Clone, Debug)]
+pub(crate) enum FilterEdit {
+    Set(String),
+    Push(char),
+    Pop
+}
+
+impl FilterEdit {
+    pub(crate) fn apply(self, mut query: String) -> String {
+        match self {
+            FilterEdit::Set(text) => return text,
+            FilterEdit::Push(c) => query.push(c),
+            FilterEdit::Pop => {
+                query.pop();
+            }
+        }
+        query
+    }
+}
+
+pub(super) fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<_> = text.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut from = 0;
+    for q in query.chars().filter(|it| !it.is_whitespace()) {
+        let i = (from..chars.len()).find(|&i| eq_ignore_case(chars[i], q))?;
+        score += MATCH_SCORE;
+        if positions.last().is_some_and(|&it| it + 1 == i) {
+            score += CONSECUTIVE_BONUS;
+        }
+        if is_boundary(&chars, i) {
+            score += BOUNDARY_BONUS;
+        }
+        positions.push(i);
+        from = i + 1;
+    }
+    let first = positions.first().copied().unwrap_or_default();
+    Some((score - first as i64, positions))
+}
+
+pub(super) fn highlight_markup(text: &str, positions: &[usize]) -> String {
+    let mut markup = String::new();
+    for (i, c) in text.chars().enumerate() {
+        let escaped = markup_escape_text(c.encode_utf8(&mut [0; 4]));
+        if positions.contains(&i) {
+            markup.push_str(&format!("<b><u>{escaped}</u></b>"));
+        } else {
+            markup.push_str(&escaped);
+        }
+    }
+    markup
+}
+
+fn eq_ignore_case(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+fn is_boundary(chars: &[char], i: usize) -> bool {
+    let Some(prev) = i.checked_sub(1).map(|it| chars[it]) else { return true };
+    !prev.is_alphanumeric() || (prev.is_lowercase() && chars[i].is_uppercase())
+}
@@ -0,0 +1,48 @@
+use std::iter::successors;
+
+use gtk::{prelude::*, FlowBox, ScrolledWindow, Widget};
+
+use super::layout::LayoutParams;
+
+pub(super) struct Grid {
+    columns: usize,
+    pub(super) page_rows: usize
+}
+
+impl Grid {
+    pub(super) fn measure(flow_box: &FlowBox, layout: &LayoutParams) -> Self {
+        let children: Vec<Widget> = successors(flow_box.first_child(), Widget::next_sibling).collect();
+        let Some(first) = children.first().filter(|it| it.width() > 0) else {
+            // Not allocated yet, e.g. while the window is not mapped
+            return Self {
+                columns: layout.columns.max(1) as usize,
+                page_rows: layout.page_rows()
+            }
+        };
+
+        let top = first.allocation().y();
+        let columns = children.iter()
+            .take_while(|it| it.allocation().y() == top)
+            .count()
+            .max(1);
+        let row_height = children.get(columns)
+            .map_or(first.height(), |it| it.allocation().y() - top)
+            .max(1);
+        let viewport_height = flow_box.ancestor(ScrolledWindow::static_type())
+            .map_or(flow_box.height(), |it| it.height());
+        Self {
+            columns,
+            page_rows: (viewport_height / row_height).max(1) as usize
+        }
+    }
+
+    pub(super) fn up(&self, cursor: usize, rows: usize) -> usize {
+        let rows = rows.min(cursor / self.columns);
+        cursor - rows * self.columns
+    }
+
+    pub(super) fn down(&self, cursor: usize, rows: usize, last: usize) -> usize {
+        let rows = rows.min(last / self.columns - cursor / self.columns);
+        (cursor + rows * self.columns).min(last)
+    }
+}
@@ -0,0 +1,126 @@
+use std::{cell::Cell, rc::Rc};
+
+use gtk::{accelerator_get_default_mod_mask, accelerator_parse, gdk::{Key, ModifierType}, glib::Propagation, prelude::*, EventControllerKey, EventControllerMotion, EventControllerScroll, EventControllerScrollFlags, EventSequenceState, GestureClick, Label, Widget};
+use log::debug;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{daemon::config::KeyConfig, protocol::{Direction, SelectMode, WindowAction}};
+
+use super::{filter::FilterEdit, GuiOp};
+
+#[derive(Clone, Debug)]
+pub(super) struct KeyBinding {
+    key: Key,
+    modifiers: ModifierType,
+    action: WindowAction
+}
+
+pub(super) fn parse_key_bindings(config: &KeyConfig) -> Vec<KeyBinding> {
+    config.bindings()
+        .into_iter()
+        .filter(|(_, accel)| !accel.is_empty())
+        .filter_map(|(action, accel)| match accelerator_parse(accel) {
+            Some((key, modifiers)) => Some(KeyBinding { key: key.to_lower(), modifiers, action }),
+            None => {
+                debug!("Invalid key for {action:?}: {accel}");
+                None
+            }
+        })
+        .collect()
+}
+
+pub(super) fn attach_tile_controllers(tile: &impl IsA<Widget>, index: &Rc<Cell<usize>>, sender: &UnboundedSender<GuiOp>) {
+    let motion = EventControllerMotion::new();
+    let last_position = Cell::new(None);
+    let s = sender.clone();
+    let i = Rc::clone(index);
+    motion.connect_motion(move |_, x, y| {
+        // The first motion only tells where the pointer rested when the switcher appeared
+        if last_position.replace(Some((x, y))).is_some_and(|it| it != (x, y)) {
+            let _ = s.send(GuiOp::SetCursor(i.get()));
+        }
+    });
+    tile.add_controller(motion);
+
+    let click = GestureClick::new();
+    let s = sender.clone();
+    let i = Rc::clone(index);
+    click.connect_released(move |gesture, _, _, _| {
+        let _ = s.send(GuiOp::SetCursor(i.get()));
+        let _ = s.send(GuiOp::SelectCurrent(select_mode(gesture.current_event_state())));
+    });
+    tile.add_controller(click);
+}
+
+pub(super) fn attach_tab_controllers(tabs: &[Label], index: &Rc<Cell<usize>>, sender: &UnboundedSender<GuiOp>) {
+    for (i, tab) in tabs.iter().enumerate() {
+        let click = GestureClick::new();
+        let s = sender.clone();
+        let index = Rc::clone(index);
+        click.connect_released(move |gesture, _, _, _| {
+            // Keep the click away from the tile, which would select its active member
+            gesture.set_state(EventSequenceState::Claimed);
+            let _ = s.send(GuiOp::SetCursor(index.get()));
+            let _ = s.send(GuiOp::SelectTab(i));
+            let _ = s.send(GuiOp::SelectCurrent(select_mode(gesture.current_event_state())));
+        });
+        tab.add_controller(click);
+    }
+}
+
+pub(super) fn attach_scroll_controller(widget: &impl IsA<Widget>, sender: &UnboundedSender<GuiOp>) {
+    let scroll = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL | EventControllerScrollFlags::DISCRETE);
+    let s = sender.clone();
+    scroll.connect_scroll(move |_, _, dy| {
+        let direction = if dy > 0.0 { Direction::Next } else { Direction::Prev };
+        let _ = s.send(GuiOp::MoveCursor(direction));
+        Propagation::Stop
+    });
+    widget.add_controller(scroll);
+}
+
+pub(super) fn attach_key_controller(widget: &impl IsA<Widget>, sender: &UnboundedSender<GuiOp>, bindings: &[KeyBinding]) {
+    let keys = EventControllerKey::new();
+    let s = sender.clone();
+    let bindings = bindings.to_vec();
+    keys.connect_key_pressed(move |_, key, _, modifiers| {
+        let mask = modifiers & accelerator_get_default_mod_mask();
+        if let Some(binding) = bindings.iter().find(|it| it.key == key.to_lower() && it.modifiers == mask) {
+            let _ = s.send(GuiOp::Action(binding.action));
+            return Propagation::Stop
+        }
+        let op = match key {
+            Key::BackSpace => GuiOp::EditFilter(FilterEdit::Pop),
+            Key::Return | Key::KP_Enter => GuiOp::SelectCurrent(select_mode(modifiers)),
+            Key::Escape => GuiOp::Close,
+            Key::Tab => GuiOp::NextTab,
+            Key::ISO_Left_Tab => GuiOp::PrevTab,
+            Key::Left => GuiOp::MoveCursor(Direction::Prev),
+            Key::Right => GuiOp::MoveCursor(Direction::Next),
+            Key::Up => GuiOp::MoveCursor(Direction::Up),
+            Key::Down => GuiOp::MoveCursor(Direction::Down),
+            Key::Home => GuiOp::MoveCursor(Direction::First),
+            Key::End => GuiOp::MoveCursor(Direction::Last),
+            Key::Page_Up => GuiOp::MoveCursor(Direction::PageUp),
+            Key::Page_Down => GuiOp::MoveCursor(Direction::PageDown),
+            _ if modifiers.intersects(ModifierType::CONTROL_MASK | ModifierType::ALT_MASK | ModifierType::SUPER_MASK) =>
+                return Propagation::Proceed,
+            _ => match key.to_unicode().filter(|it| !it.is_control()) {
+                Some(c) => GuiOp::EditFilter(FilterEdit::Push(c)),
+                None => return Propagation::Proceed
+            }
+        };
+        let _ = s.send(op);
+        Propagation::Stop
+    });
+    widget.add_controller(keys);
+}
+
+// Holding Shift brings the window to the current workspace instead of travelling to it
+fn select_mode(modifiers: ModifierType) -> SelectMode {
+    if modifiers.contains(ModifierType::SHIFT_MASK) {
+        SelectMode::BringHere
+    } else {
+        SelectMode::Focus
+    }
+}
@@ -0,0 +1,127 @@
+use gtk::Orientation;
+
+use crate::daemon::config::Layout;
+
+const TILE_PADDING: i32 = 24;
+const LABEL_HEIGHT: i32 = 24;
+const MIN_ICON_SIZE: i32 = 32;
+const ICON_STEP: i32 = 8;
+// Margins around a thumbnail, on both sides
+const THUMBNAIL_MARGIN: i32 = 16;
+const FALLBACK_MONITOR_SIZE: (i32, i32) = (1920, 1080);
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct LayoutParams {
+    pub(super) columns: u32,
+    pub(super) icon_size: i32,
+    pub(super) orientation: Orientation,
+    pub(super) max_height: i32,
+    // Window tiles show 16:9 thumbnails instead of square icons
+    pub(super) thumbnails: bool
+}
+
+impl LayoutParams {
+    pub(super) fn compute(layout: Layout, count: usize, icon_size: u16, thumbnails: bool, monitor: Option<(i32, i32)>) -> Self {
+        let (width, height) = monitor.unwrap_or(FALLBACK_MONITOR_SIZE);
+        let (width, height) = (width * 9 / 10, height * 8 / 10);
+        let count = count.max(1) as i32;
+        let icon_size = icon_size as i32;
+
+        match layout {
+            Layout::Horizontal => {
+                // Shrink the icons so that every tile fits in a single row, like GNOME does
+                let icon_size = fitting_icon_size(width / count, thumbnails).clamp(MIN_ICON_SIZE, icon_size.max(MIN_ICON_SIZE));
+                Self {
+                    columns: (width / tile_width(icon_size, thumbnails)).clamp(1, count) as u32,
+                    icon_size,
+                    orientation: Orientation::Vertical,
+                    max_height: height,
+                    thumbnails
+                }
+            }
+            Layout::Grid => {
+                // Shrink the icons until every row fits on the monitor
+                let mut icon_size = icon_size.max(MIN_ICON_SIZE);
+                let columns = loop {
+                    let columns = (width / tile_width(icon_size, thumbnails)).clamp(1, count);
+                    let rows = (count + columns - 1) / columns;
+                    if rows * tile_height(icon_size, thumbnails) <= height || icon_size <= MIN_ICON_SIZE {
+                        break columns
+                    }
+                    icon_size = (icon_size - ICON_STEP).max(MIN_ICON_SIZE);
+                };
+                Self {
+                    columns: columns as u32,
+                    icon_size,
+                    orientation: Orientation::Vertical,
+                    max_height: height,
+                    thumbnails
+                }
+            }
+            Layout::List => Self {
+                columns: 1,
+                icon_size: (icon_size / 2).max(MIN_ICON_SIZE),
+                orientation: Orientation::Horizontal,
+                max_height: height,
+                thumbnails
+            }
+        }
+    }
+
+    // What fits in the scrolled area before anything is allocated to measure
+    pub(super) fn page_rows(&self) -> usize {
+        let row_height = match self.orientation {
+            Orientation::Horizontal => tile_height(self.icon_size, self.thumbnails) - LABEL_HEIGHT,
+            _ => tile_height(self.icon_size, self.thumbnails)
+        };
+        (self.max_height / row_height).max(1) as usize
+    }
+}
+
+// The content of a tile is `icon_size` high, and as wide unless it is a thumbnail
+const fn tile_width(icon_size: i32, thumbnails: bool) -> i32 {
+    if thumbnails {
+        icon_size * 16 / 9 + THUMBNAIL_MARGIN + TILE_PADDING
+    } else {
+        icon_size + TILE_PADDING
+    }
+}
+
+const fn tile_height(icon_size: i32, thumbnails: bool) -> i32 {
+    if thumbnails {
+        icon_size + THUMBNAIL_MARGIN + TILE_PADDING + LABEL_HEIGHT
+    } else {
+        icon_size + TILE_PADDING + LABEL_HEIGHT
+    }
+}
+
+// The largest icon size whose tiles are at most `width` wide
+const fn fitting_icon_size(width: i32, thumbnails: bool) -> i32 {
+    if thumbnails {
+        (width - THUMBNAIL_MARGIN - TILE_PADDING) * 9 / 16
+    } else {
+        width - TILE_PADDING
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::daemon::config::Layout;
+
+    use super::{tile_width, LayoutParams};
+
+    #[test]
+    fn thumbnails_fit_in_a_row() {
+        let layout = LayoutParams::compute(Layout::Horizontal, 8, 96, true, Some((1920, 1080)));
+        assert_eq!(layout.columns, 8);
+        assert!(8 * tile_width(layout.icon_size, true) <= 1920 * 9 / 10);
+    }
+
+    #[test]
+    fn thumbnail_grid_has_fewer_columns() {
+        let icons = LayoutParams::compute(Layout::Grid, 30, 96, false, Some((1920, 1080)));
+        let thumbnails = LayoutParams::compute(Layout::Grid, 30, 96, true, Some((1920, 1080)));
+        assert!(thumbnails.columns < icons.columns);
+        assert!(thumbnails.columns as i32 * tile_width(thumbnails.icon_size, true) <= 1920 * 9 / 10);
+    }
+}
@@ -1,36 +1,110 @@
 mod css;
+mod filter;
+mod grid;
+mod input;
+mod layout;
+mod tile;
 
-use std::sync::{Arc, Mutex};
+use std::{cmp::Reverse, collections::{HashMap, HashSet}, mem, sync::{Arc, Mutex}, time::Duration};
 
-use css::DEFAULT_CSS;
+use css::{fade_css, DEFAULT_CSS};
 use either::Either;
-use gtk::{style_context_add_provider_for_display, gdk, gio::{spawn_blocking, ApplicationHoldGuard}, prelude::*, Application, ApplicationWindow, CssProvider, FlowBox, Frame, Image, Overlay, STYLE_PROVIDER_PRIORITY_USER};
+use hyprland::shared::{Address, WorkspaceId};
+use grid::Grid;
+pub(super) use filter::FilterEdit;
+use gtk::{style_context_add_provider_for_display, gdk, gio::{spawn_blocking, ApplicationHoldGuard}, prelude::*, Application, ApplicationWindow, CssProvider, FlowBox, FlowBoxChild, Label, PolicyType, ScrolledWindow, Widget, Orientation, Overlay, Settings, pango::EllipsizeMode, STYLE_PROVIDER_PRIORITY_USER};
 use anyhow::{anyhow, Result, Context as _};
-use gtk_layer_shell::{Layer, LayerShell as _};
-use tokio::sync::{mpsc::UnboundedReceiver, Notify};
+use gtk_layer_shell::{Edge, KeyboardMode, Layer, LayerShell as _};
+use log::debug;
+use layout::LayoutParams;
+use input::{attach_key_controller, attach_scroll_controller, attach_tab_controllers, attach_tile_controllers, parse_key_bindings, KeyBinding};
+use tokio::sync::{mpsc::{UnboundedReceiver, UnboundedSender}, oneshot, Notify};
+use tile::{TextureCache, Tile, TileEntry, Tiles, ACTIVE_TAB_CLASS};
 use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
 
-use crate::protocol::{Direction, Launch};
+use crate::protocol::{Direction, DmenuItem, Launch, SelectMode, WindowAction};
 
-use super::{app_manager::{AppManager, Applications}, icon::lookup_icon, wayland::activate_window};
+use super::{app_info::AppInfoCache, app_manager::{AppManager, Applications, Window}, config::{Anchor, Config, Keyboard, LayerShellConfig, ShellLayer}, desktop::DesktopEntry, launcher::{launch_entry, LaunchHistory}, provider::{collect_items, invoke_action, ProviderItem}, thumbnail::Thumbnails, wayland::{activate_window, active_monitor_scale, active_monitor_size, active_window, active_workspace, bring_window, dispatch_action}};
 
 const CURRENT_ITEM_CLASS: &str = "current-item";
+const CAPTION_CLASS: &str = "switcher-caption";
+const CAPTION_WIDTH: i32 = 48;
+const FILTER_CLASS: &str = "switcher-filter";
+const WINDOW_CLASS: &str = "switcher";
+const SHOWN_CLASS: &str = "shown";
+const LAYER_NAMESPACE: &str = "sagi";
+const REFRESH_DELAY: Duration = Duration::from_millis(50);
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub(super) enum GuiOp {
     Launch(Launch),
+    Dmenu(Vec<DmenuItem>, oneshot::Sender<Option<String>>),
+    Provided(u64, Vec<(usize, ProviderItem)>),
     MoveCursor(Direction),
+    SetCursor(usize),
+    SelectTab(usize),
+    NextTab,
+    PrevTab,
+    SelectIndex(usize, SelectMode),
+    EditFilter(FilterEdit),
     ShowWindows,
-    SelectCurrent
+    SelectCurrent(SelectMode),
+    Action(WindowAction),
+    Close,
+    Show(u64),
+    AppsChanged,
+    RefreshTiles
 }
 
 type SwitcherContext = Either<Closed, Open>;
 
 struct Open {
+    widgets: Widgets,
+    source: Source,
+    view: View,
+    tiles: Vec<Tile>,
+    query: String,
+    order: Vec<usize>,
+    cursor: usize,
+    generation: u64,
+    peek: Option<Peek>,
+    // The group member picked in the window list; ignored unless it belongs to the current entry
+    tab: Option<Address>
+}
+
+struct Peek {
+    original: Option<Address>,
+    focused: Option<Address>
+}
+
+struct Widgets {
     window: ApplicationWindow,
+    scrolled: ScrolledWindow,
+    flow_box: FlowBox,
+    caption: Label,
+    filter_label: Label,
+    layout: LayoutParams
+}
+
+#[derive(Default)]
+struct Source {
     apps: Applications,
-    frames: Vec<Frame>,
-    cursor: usize
+    entries: Vec<DesktopEntry>,
+    items: Vec<DmenuItem>,
+    provided: Vec<(usize, ProviderItem)>,
+    reply: Option<oneshot::Sender<Option<String>>>,
+    // The workspace the switcher was opened on, before peeking moved away from it
+    workspace: Option<WorkspaceId>
+}
+
+// What opening a switcher needs from the GUI task
+struct GuiEnv<'a> {
+    app: &'a Application,
+    sender: &'a UnboundedSender<GuiOp>,
+    config: &'a Config,
+    bindings: &'a [KeyBinding],
+    app_info: &'a Mutex<AppInfoCache>,
+    thumbnails: &'a Mutex<Thumbnails>
 }
 
 struct Closed {
@@ -38,13 +112,93 @@ struct Closed {
     hold_guard: ApplicationHoldGuard
 }
 
-const fn new_open_ctx(window: ApplicationWindow, frames: Vec<Frame>, apps: Applications) -> SwitcherContext {
-    Either::Right(Open{
-        window,
-        apps,
-        frames,
-        cursor: 0
-    })
+#[derive(Clone, Copy, Debug)]
+enum View {
+    Apps,
+    Windows(usize),
+    Launcher,
+    Dmenu,
+    Provider
+}
+
+// Builds the window and the tiles of any kind of switcher, and maps it after `show_delay` milliseconds
+fn open_switcher(env: &GuiEnv, textures: &mut TextureCache, source: Source, view: View, generation: u64, show_delay: u64) -> Open {
+    let mut app_info = env.app_info.lock().unwrap();
+    app_info.set_icon_scale(active_monitor_scale());
+    let layout = compute_layout(env.config, &source, view, env.thumbnails);
+    let widgets = create_window(env.app, env.sender, layout, &env.config.layer_shell, env.bindings);
+    let tiles = create_tiles(&source, view, env.sender, &mut Tiles {
+        app_info: &mut app_info,
+        thumbnails: &env.thumbnails.lock().unwrap(),
+        textures,
+        layout: &widgets.layout
+    });
+    if show_delay > 0 {
+        let sender = env.sender.clone();
+        glib::timeout_add_local_once(Duration::from_millis(show_delay), move || {
+            let _ = sender.send(GuiOp::Show(generation));
+        });
+    } else {
+        show_window(&widgets.window);
+    }
+    let open = Open {
+        widgets,
+        source,
+        view,
+        order: (0..tiles.len()).collect(),
+        tiles,
+        query: String::new(),
+        cursor: 0,
+        generation,
+        peek: None,
+        tab: None
+    };
+    show_tiles(&open.widgets, &open.tiles, &open.order);
+    open
+}
+
+fn place_cursor(open: Open, cursor: usize) -> Open {
+    let cursor = cursor.min(open.order.len().saturating_sub(1));
+    if cursor != open.cursor {
+        highlight(&open, cursor);
+    }
+    Open {
+        cursor,
+        ..open
+    }
+}
+
+impl Open {
+    fn current_app(&self) -> Option<&[Window]> {
+        let current = *self.order.get(self.cursor)?;
+        let app = match self.view {
+            View::Apps | View::Launcher => current,
+            View::Windows(app) => app,
+            View::Dmenu | View::Provider => return None
+        };
+        self.source.apps.get(app).map(|(_, windows)| windows.as_slice())
+    }
+
+    fn current_window(&self) -> Option<&Window> {
+        let current = *self.order.get(self.cursor)?;
+        let apps = &self.source.apps;
+        match self.view {
+            View::Apps | View::Launcher => apps.get(current).and_then(|(_, windows)| windows.first()),
+            View::Windows(app) => {
+                let (_, windows) = apps.get(app)?;
+                let entry = window_entries(windows).into_iter().nth(current)?;
+                entry.iter()
+                    .find(|&&it| Some(windows[it].addr()) == self.tab.as_ref())
+                    .or_else(|| entry.iter().min())
+                    .map(|&it| &windows[it])
+            }
+            View::Dmenu | View::Provider => None
+        }
+    }
+
+    fn tile_key(&self, tile: usize) -> Option<String> {
+        tile_key(&self.source, self.view, tile)
+    }
 }
 
 fn new_closed_ctx(app: &Application) -> SwitcherContext {
@@ -53,7 +207,7 @@ fn new_closed_ctx(app: &Application) -> SwitcherContext {
     })
 }
 
-pub(super) async fn start_gui(app_manager: Arc<Mutex<AppManager>>, receiver: UnboundedReceiver<GuiOp>) -> Result<()> {
+pub(super) async fn start_gui(config: Config, app_manager: Arc<Mutex<AppManager>>, app_info: Arc<Mutex<AppInfoCache>>, thumbnails: Arc<Mutex<Thumbnails>>, sender: UnboundedSender<GuiOp>, receiver: UnboundedReceiver<GuiOp>) -> Result<()> {
     spawn_blocking(move || {
         let app = create_application();
 
@@ -61,8 +215,9 @@ pub(super) async fn start_gui(app_manager: Arc<Mutex<AppManager>>, receiver: Unb
         let activation_notify = Arc::new(Notify::new());
         let activation_notify2 = activation_notify.clone();
 
+        let fade_duration = config.fade_duration;
         app.connect_activate(move |_| {
-            load_css();
+            load_css(fade_duration);
             activation_notify.notify_one();
         });
 
@@ -70,46 +225,307 @@ pub(super) async fn start_gui(app_manager: Arc<Mutex<AppManager>>, receiver: Unb
         let app2 = app.clone();
         glib::spawn_future_local(async move {
             activation_notify2.notified().await;
+            watch_icon_theme(&app_info);
+            let mut textures = TextureCache::new();
+            let mut history = LaunchHistory::load();
+            let mut generation = 0;
+            let mut refresh_pending = false;
+            let bindings = parse_key_bindings(&config.keys);
+            let env = GuiEnv {
+                app: &app2,
+                sender: &sender,
+                config: &config,
+                bindings: &bindings,
+                app_info: &app_info,
+                thumbnails: &thumbnails
+            };
 
             UnboundedReceiverStream::new(receiver)
                 .fold(ctx, |ctx, op| match op {
-                    GuiOp::Launch(launch) => ctx.left_and_then(|_| {
-                        let apps = app_manager.lock().unwrap().get_apps().clone();
-                        let (window, frames) = create_window(&app2, &apps);
-                        window.show();
-                        new_open_ctx(window, frames, apps)
-                    }),
-                    GuiOp::MoveCursor(direction) => ctx.map_right(|Open { apps, frames, cursor, window }| {
-                        frames[cursor].remove_css_class(CURRENT_ITEM_CLASS);
+                    GuiOp::Launch(launch) => ctx.left_and_then(|closed| {
+                        generation += 1;
+                        let quick_switch = matches!(launch, Launch::App | Launch::WindowInApp);
+                        let view = match launch {
+                            Launch::App => View::Apps,
+                            Launch::WindowInApp => View::Windows(0),
+                            Launch::Launcher => View::Launcher,
+                            Launch::Provider(mode) => {
+                                // Providers run outside the main loop; the switcher opens once they answer
+                                let providers = config.provider.clone();
+                                let sender = sender.clone();
+                                let requested = generation;
+                                spawn_blocking(move || {
+                                    let _ = sender.send(GuiOp::Provided(requested, collect_items(&providers, &mode)));
+                                });
+                                return Either::Left(closed)
+                            }
+                        };
+                        let apps = snapshot_apps(&app_manager, &config);
+                        let entries = {
+                            let mut app_info = app_info.lock().unwrap();
+                            app_info.refresh();
+                            match view {
+                                View::Launcher => launcher_entries(&app_info, &apps, &history),
+                                _ => Vec::new()
+                            }
+                        };
+                        let source = Source {
+                            apps,
+                            entries,
+                            workspace: active_workspace(),
+                            ..Source::default()
+                        };
+                        // Committing before the delay elapses switches without ever mapping the window
+                        let show_delay = if quick_switch { config.show_delay } else { 0 };
+                        let open = open_switcher(&env, &mut textures, source, view, generation, show_delay);
+                        let cursor = match view {
+                            View::Apps | View::Windows(_) | View::Launcher if config.start_on_previous => 1,
+                            _ => 0
+                        };
+                        let peek = config.peek.then(|| Peek {
+                            original: active_window(),
+                            focused: None
+                        });
+                        // Peeking focuses other windows, which would take the keyboard from an on-demand surface
+                        if peek.is_some() && matches!(config.layer_shell.keyboard, Keyboard::OnDemand) {
+                            open.widgets.window.set_keyboard_mode(KeyboardMode::Exclusive);
+                        }
+                        // The cursor may start past the active window
+                        let mut open = place_cursor(Open { peek, ..open }, cursor);
+                        peek_current(&mut open, &app_manager);
+                        Either::Right(open)
+                    }),
+                    GuiOp::Dmenu(items, reply) => ctx.left_and_then(|_| {
+                        generation += 1;
+                        let source = Source {
+                            items,
+                            reply: Some(reply),
+                            ..Source::default()
+                        };
+                        Either::Right(open_switcher(&env, &mut textures, source, View::Dmenu, generation, 0))
+                    }),
+                    GuiOp::Provided(requested, provided) => ctx.left_and_then(|closed| {
+                        // Another switcher was requested while the providers were running
+                        if requested != generation {
+                            return Either::Left(closed)
+                        }
+                        let source = Source {
+                            provided,
+                            ..Source::default()
+                        };
+                        Either::Right(open_switcher(&env, &mut textures, source, View::Provider, generation, 0))
+                    }),
+                    GuiOp::MoveCursor(direction) => ctx.map_right(|open| {
+                        if open.order.is_empty() {
+                            return open
+                        }
+                        let last = open.order.len() - 1;
+                        let grid = Grid::measure(&open.widgets.flow_box, &open.widgets.layout);
                         let cursor = match direction {
                             Direction::Prev => 
-                                if cursor == 0 {
-                                    apps.len() - 1
+                                if open.cursor == 0 {
+                                    last
                                 } else {
-                                    cursor - 1
+                                    open.cursor - 1
                                 },
                             Direction::Next =>
-                                if cursor == apps.len() - 1 {
+                                if open.cursor == last {
                                     0
                                 } else {
-                                    cursor + 1
+                                    open.cursor + 1
                                 },
+                            Direction::Up => grid.up(open.cursor, 1),
+                            Direction::Down => grid.down(open.cursor, 1, last),
+                            Direction::First => 0,
+                            Direction::Last => last,
+                            Direction::PageUp => grid.up(open.cursor, grid.page_rows),
+                            Direction::PageDown => grid.down(open.cursor, grid.page_rows, last)
                         };
-                        frames[cursor].add_css_class(CURRENT_ITEM_CLASS);
-                        Open {
-                            apps,
-                            frames,
-                            window,
-                            cursor
+                        highlight(&open, cursor);
+                        let mut open = Open {
+                            cursor,
+                            ..open
+                        };
+                        peek_current(&mut open, &app_manager);
+                        open
+                    }),
+                    GuiOp::SetCursor(tile) => ctx.map_right(|open| {
+                        let Some(cursor) = open.order.iter().position(|&it| it == tile) else {
+                            return open
+                        };
+                        if cursor == open.cursor {
+                            return open
+                        }
+                        highlight(&open, cursor);
+                        let mut open = Open {
+                            cursor,
+                            ..open
+                        };
+                        peek_current(&mut open, &app_manager);
+                        open
+                    }),
+                    GuiOp::SelectTab(tab) => ctx.map_right(|open| {
+                        let mut open = switch_tab(open, |_, _| tab);
+                        peek_current(&mut open, &app_manager);
+                        open
+                    }),
+                    GuiOp::NextTab => ctx.map_right(|open| {
+                        let mut open = switch_tab(open, |tab, len| (tab + 1) % len);
+                        peek_current(&mut open, &app_manager);
+                        open
+                    }),
+                    GuiOp::PrevTab => ctx.map_right(|open| {
+                        let mut open = switch_tab(open, |tab, len| (tab + len - 1) % len);
+                        peek_current(&mut open, &app_manager);
+                        open
+                    }),
+                    GuiOp::EditFilter(edit) => ctx.map_right(|open| {
+                        let query = edit.apply(open.query);
+                        let order = filter_order(&open.tiles, &query);
+                        show_tiles(&open.widgets, &open.tiles, &order);
+                        open.widgets.filter_label.set_label(&query);
+                        open.widgets.filter_label.set_visible(!query.is_empty());
+                        let mut open = Open {
+                            query,
+                            order,
+                            cursor: 0,
+                            ..open
+                        };
+                        peek_current(&mut open, &app_manager);
+                        open
+                    }),
+                    GuiOp::ShowWindows => ctx.map_right(|mut open| match (open.view, open.order.get(open.cursor)) {
+                        (View::Apps | View::Launcher, Some(&app)) if app < open.source.apps.len() => {
+                            let view = View::Windows(app);
+                            apply_layout(&mut open.widgets, compute_layout(&config, &open.source, view, &thumbnails));
+                            let tiles = create_tiles(&open.source, view, &sender, &mut Tiles {
+                                app_info: &mut app_info.lock().unwrap(),
+                                thumbnails: &thumbnails.lock().unwrap(),
+                                textures: &mut textures,
+                                layout: &open.widgets.layout
+                            });
+                            let order: Vec<_> = (0..tiles.len()).collect();
+                            show_tiles(&open.widgets, &tiles, &order);
+                            open.widgets.filter_label.set_visible(false);
+                            let mut open = Open {
+                                view,
+                                order,
+                                tiles,
+                                query: String::new(),
+                                cursor: 0,
+                                ..open
+                            };
+                            peek_current(&mut open, &app_manager);
+                            open
                         }
+                        _ => open
                     }),
-                    GuiOp::ShowWindows => todo!(),
-                    GuiOp::SelectCurrent => ctx.right_and_then(|Open { window, apps, cursor, .. }| {
-                        let (_, windows) = &apps[cursor];
-                        let _ = activate_window(windows[0].addr().clone());
-                        window.close();
+                    GuiOp::SelectCurrent(mode) => ctx.right_and_then(|open| {
+                        select_current(open, mode, &config, &app_manager, &mut history);
                         new_closed_ctx(&app2)
-                    })
+                    }),
+                    GuiOp::SelectIndex(position, mode) => ctx.right_and_then(|open| {
+                        if position >= open.order.len() {
+                            return Either::Right(open)
+                        }
+                        select_current(place_cursor(open, position), mode, &config, &app_manager, &mut history);
+                        new_closed_ctx(&app2)
+                    }),
+                    GuiOp::Action(action) => ctx.map_right(|open| {
+                        run_action(&open, action);
+                        open
+                    }),
+                    GuiOp::Close => ctx.right_and_then(|Open { widgets, peek, .. }| {
+                        if let Some(original) = finish_peek(peek, None, &app_manager) {
+                            let _ = activate_window(original, config.leave_fullscreen);
+                        }
+                        close_window(widgets.window, config.fade_duration);
+                        new_closed_ctx(&app2)
+                    }),
+                    GuiOp::Show(shown) => ctx.map_right(|open| {
+                        if open.generation == shown {
+                            show_window(&open.widgets.window);
+                        }
+                        open
+                    }),
+                    GuiOp::AppsChanged => ctx.map_right(|open| {
+                        // Titles may change several times a second, so a burst of events is applied at once
+                        if !refresh_pending && !matches!(open.view, View::Dmenu | View::Provider) {
+                            refresh_pending = true;
+                            let sender = sender.clone();
+                            glib::timeout_add_local_once(REFRESH_DELAY, move || {
+                                let _ = sender.send(GuiOp::RefreshTiles);
+                            });
+                        }
+                        open
+                    }),
+                    GuiOp::RefreshTiles => {
+                        refresh_pending = false;
+                        ctx.map_right(|mut open| {
+                            if matches!(open.view, View::Dmenu | View::Provider) {
+                                return open
+                            }
+                            let key = open.order.get(open.cursor).and_then(|&it| open.tile_key(it));
+                            let old_len = source_len(&open.source, open.view);
+                            let apps = snapshot_apps(&app_manager, &config);
+                            let view = match open.view {
+                                // Fall back to the app list when the last window of the app was closed
+                                View::Windows(app) => open.source.apps.get(app)
+                                    .and_then(|(app_id, _)| apps.iter().position(|(it, _)| it == app_id))
+                                    .map_or(View::Apps, View::Windows),
+                                view => view
+                            };
+                            let old_keys: Vec<_> = (0..open.tiles.len()).map(|it| open.tile_key(it)).collect();
+                            let mut old: HashMap<_, _> = old_keys.into_iter()
+                                .zip(mem::take(&mut open.tiles))
+                                .filter_map(|(key, tile)| Some((key?, tile)))
+                                .collect();
+                            let mut app_info = app_info.lock().unwrap();
+                            let entries = match view {
+                                // The desktop entries only depend on which apps are running
+                                View::Launcher if same_app_ids(&open.source.apps, &apps) => mem::take(&mut open.source.entries),
+                                View::Launcher => launcher_entries(&app_info, &apps, &history),
+                                _ => Vec::new()
+                            };
+                            let source = Source {
+                                apps,
+                                entries,
+                                workspace: open.source.workspace,
+                                ..Source::default()
+                            };
+                            let len = source_len(&source, view);
+                            if len != old_len {
+                                let layout = compute_layout(&config, &source, view, &thumbnails);
+                                // Tiles built for another icon size cannot be kept
+                                if layout.icon_size != open.widgets.layout.icon_size {
+                                    old.clear();
+                                }
+                                apply_layout(&mut open.widgets, layout);
+                            }
+                            let tiles = update_tiles(&source, view, old, &sender, &mut Tiles {
+                                app_info: &mut app_info,
+                                thumbnails: &thumbnails.lock().unwrap(),
+                                textures: &mut textures,
+                                layout: &open.widgets.layout
+                            });
+                            let order = filter_order(&tiles, &open.query);
+                            sync_tiles(&open.widgets, &tiles, &order);
+                            let cursor = key.and_then(|key| order.iter().position(|&it| tile_key(&source, view, it).as_ref() == Some(&key)))
+                                .unwrap_or(open.cursor)
+                                .min(order.len().saturating_sub(1));
+                            let open = Open {
+                                source,
+                                view,
+                                tiles,
+                                order,
+                                cursor,
+                                ..open
+                            };
+                            open.widgets.caption.set_label("");
+                            highlight(&open, cursor);
+                            open
+                        })
+                    }
                 }).await;
 
             // while let Some(op) = receiver.recv().await {
@@ -148,54 +564,447 @@ pub(super) async fn start_gui(app_manager: Arc<Mutex<AppManager>>, receiver: Unb
     }).await.map_err(|_| anyhow!("Gui task was failed"))
 }
 
-fn create_window(app: &Application, apps: &Applications) -> (ApplicationWindow, Vec<Frame>) {
+fn create_window(app: &Application, sender: &UnboundedSender<GuiOp>, layout: LayoutParams, layer_shell: &LayerShellConfig, bindings: &[KeyBinding]) -> Widgets {
     let flow_box = FlowBox::builder()
         .selection_mode(gtk::SelectionMode::None)
         .orientation(gtk::Orientation::Horizontal)
-        .max_children_per_line(16)
-        .min_children_per_line(16)
+        .max_children_per_line(layout.columns)
+        .min_children_per_line(layout.columns)
         .build();
-
-    let mut frames = Vec::new();
-    for (class_name, _) in apps {
-        let app_frame = app_frame(class_name);
-        flow_box.insert(&app_frame, -1);
-        frames.push(app_frame);
-    }
-    if let Some(first_frame) = frames.first() {
-        first_frame.add_css_class(CURRENT_ITEM_CLASS);
-    }
+    let scrolled = ScrolledWindow::builder()
+        .child(&flow_box)
+        .hscrollbar_policy(PolicyType::Never)
+        .vscrollbar_policy(PolicyType::Automatic)
+        .propagate_natural_width(true)
+        .propagate_natural_height(true)
+        .max_content_height(layout.max_height)
+        .build();
+    let caption = Label::builder()
+        .css_classes(vec![CAPTION_CLASS])
+        .ellipsize(EllipsizeMode::Middle)
+        .max_width_chars(CAPTION_WIDTH)
+        .single_line_mode(true)
+        .build();
+    let filter_label = Label::builder()
+        .css_classes(vec![FILTER_CLASS])
+        .ellipsize(EllipsizeMode::Start)
+        .max_width_chars(CAPTION_WIDTH)
+        .single_line_mode(true)
+        .visible(false)
+        .build();
+    let container = gtk::Box::builder()
+        .orientation(Orientation::Vertical)
+        .build();
+    container.append(&filter_label);
+    container.append(&scrolled);
+    container.append(&caption);
 
     let overlay = Overlay::builder()
-        .child(&flow_box)
+        .child(&container)
         .build();
     let window = ApplicationWindow::builder()
         .application(app)
         .child(&overlay)
         .default_height(10)
         .default_width(10)
+        .css_classes(vec![WINDOW_CLASS])
         .build();
+    attach_scroll_controller(&window, sender);
+    attach_key_controller(&window, sender, bindings);
+    configure_layer_shell(&window, layer_shell);
+    Widgets {
+        window,
+        scrolled,
+        flow_box,
+        caption,
+        filter_label,
+        layout
+    }
+}
+
+// Thumbnails are wider than icons, so fewer window tiles fit in a row
+fn compute_layout(config: &Config, source: &Source, view: View, thumbnails: &Mutex<Thumbnails>) -> LayoutParams {
+    let thumbnails = matches!(view, View::Windows(_)) && thumbnails.lock().unwrap().available();
+    LayoutParams::compute(config.layout, source_len(source, view), config.icon_size, thumbnails, active_monitor_size())
+}
+
+fn apply_layout(widgets: &mut Widgets, layout: LayoutParams) {
+    widgets.flow_box.set_max_children_per_line(layout.columns);
+    widgets.flow_box.set_min_children_per_line(layout.columns);
+    widgets.scrolled.set_max_content_height(layout.max_height);
+    widgets.layout = layout;
+}
+
+fn select_current(open: Open, mode: SelectMode, config: &Config, app_manager: &Mutex<AppManager>, history: &mut LaunchHistory) {
+    let target = open.current_window().map(|it| it.addr().clone());
+    let restore = finish_peek(open.peek, target.as_ref(), app_manager);
+    let Open { widgets, source, view, order, cursor, .. } = open;
+    let Source { apps, entries, items, provided, reply, workspace: source_workspace } = source;
+    let current = order.get(cursor).copied();
+    if let (Some(reply), Some(item)) = (reply, current.and_then(|it| items.get(it))) {
+        let _ = reply.send(Some(item.text.clone()));
+    }
+    if let (View::Provider, Some((provider, item))) = (view, current.and_then(|it| provided.get(it))) {
+        if let Err(e) = invoke_action(&config.provider, *provider, item) {
+            debug!("Failed to invoke the action of {}: {e:?}", item.label());
+        }
+    }
+    if let Some(target) = target {
+        if app_manager.lock().unwrap().contains(&target) {
+            let _ = match (mode, source_workspace) {
+                (SelectMode::BringHere, Some(workspace)) => bring_window(target, workspace, config.leave_fullscreen),
+                _ => activate_window(target, config.leave_fullscreen)
+            };
+        } else {
+            debug!("Window {target} has already been closed");
+        }
+    } else if let Some(original) = restore {
+        let _ = activate_window(original, config.leave_fullscreen);
+    }
+    // The entries are kept while listing the windows of an app, where the positions mean windows
+    if let (View::Launcher, Some(entry)) = (view, current.and_then(|it| it.checked_sub(apps.len())).and_then(|it| entries.get(it))) {
+        match launch_entry(entry) {
+            Ok(()) => history.record(entry.app_id()),
+            Err(e) => debug!("Failed to launch {}: {e:?}", entry.app_id())
+        }
+    }
+    close_window(widgets.window, config.fade_duration);
+}
+
+fn switch_tab(open: Open, to: impl FnOnce(usize, usize) -> usize) -> Open {
+    let View::Windows(app) = open.view else { return open };
+    let Some(&current) = open.order.get(open.cursor) else { return open };
+    let Some((_, windows)) = open.source.apps.get(app) else { return open };
+    let Some(entry) = window_entries(windows).into_iter().nth(current) else { return open };
+    let Some(tab) = open.current_window().and_then(|window| entry.iter().position(|&it| windows[it].addr() == window.addr())) else {
+        return open
+    };
+    if entry.len() < 2 {
+        return open
+    }
+    let tab = to(tab, entry.len()).min(entry.len() - 1);
+    let window = &windows[entry[tab]];
+    for (i, label) in open.tiles[current].tabs.iter().enumerate() {
+        if i == tab {
+            label.add_css_class(ACTIVE_TAB_CLASS);
+        } else {
+            label.remove_css_class(ACTIVE_TAB_CLASS);
+        }
+    }
+    open.widgets.caption.set_label(window.title());
+    let tab = Some(window.addr().clone());
+    Open {
+        tab,
+        ..open
+    }
+}
+
+fn run_action(open: &Open, action: WindowAction) {
+    let targets: Vec<_> = match action {
+        WindowAction::CloseApp => open.current_app().unwrap_or_default().iter().collect(),
+        _ => open.current_window().into_iter().collect()
+    };
+    // The tiles are rebuilt once Hyprland reports the change
+    for window in targets {
+        if let Err(e) = dispatch_action(action, window.addr().clone(), open.source.workspace) {
+            debug!("Failed to {action:?} window {}: {e:?}", window.addr());
+        }
+    }
+}
+
+// Never leaves fullscreen, since that would not be undone when moving on to the next window
+fn peek_current(open: &mut Open, app_manager: &Mutex<AppManager>) {
+    let Some(addr) = open.current_window().map(|it| it.addr().clone()) else { return };
+    let Some(peek) = &mut open.peek else { return };
+    if peek.focused.as_ref() == Some(&addr) {
+        return
+    }
+    if peek.focused.is_none() {
+        app_manager.lock().unwrap().begin_peek();
+    }
+    match activate_window(addr.clone(), false) {
+        Ok(()) => peek.focused = Some(addr),
+        Err(e) => debug!("Failed to peek window {addr}: {e:?}")
+    }
+}
+
+// Returns the window which has to be focused to settle the focus changed by peeking
+fn finish_peek(peek: Option<Peek>, target: Option<&Address>, app_manager: &Mutex<AppManager>) -> Option<Address> {
+    let Peek { original, focused } = peek?;
+    let mut app_manager = app_manager.lock().unwrap();
+    // Peeking has begun even if focusing the first window failed
+    let Some(focused) = focused else {
+        app_manager.end_peek(None);
+        return None
+    };
+    let settle = target.cloned().or(original);
+    app_manager.end_peek(settle.clone());
+    settle.filter(|it| *it != focused)
+}
+
+fn configure_layer_shell(window: &ApplicationWindow, config: &LayerShellConfig) {
     window.init_layer_shell();
-    window.set_layer(Layer::Overlay);
+    window.set_namespace(LAYER_NAMESPACE);
+    window.set_layer(match config.layer {
+        ShellLayer::Background => Layer::Background,
+        ShellLayer::Bottom => Layer::Bottom,
+        ShellLayer::Top => Layer::Top,
+        ShellLayer::Overlay => Layer::Overlay
+    });
+    match config.anchor {
+        Anchor::Center => {}
+        Anchor::Top => window.set_anchor(Edge::Top, true),
+        Anchor::Bottom => window.set_anchor(Edge::Bottom, true)
+    }
+    window.set_margin(Edge::Top, config.margin.top);
+    window.set_margin(Edge::Bottom, config.margin.bottom);
+    window.set_margin(Edge::Left, config.margin.left);
+    window.set_margin(Edge::Right, config.margin.right);
+    window.set_exclusive_zone(config.exclusive_zone);
+    window.set_keyboard_mode(match config.keyboard {
+        Keyboard::None => KeyboardMode::None,
+        Keyboard::Exclusive => KeyboardMode::Exclusive,
+        Keyboard::OnDemand => KeyboardMode::OnDemand
+    });
+}
+
+fn show_window(window: &ApplicationWindow) {
     window.present();
-    (window, frames)
-}
-
-fn app_frame(class_name: &str) -> Frame {
-    let icon_path = lookup_icon(class_name).and_then(|it| it.into_os_string().into_string().ok());
-    let icon_image = Image::builder()
-        .file(&icon_path.unwrap_or("".to_string()))
-        .height_request(96)
-        .width_request(96)
-        .margin_top(8)
-        .margin_bottom(8)
-        .margin_start(8)
-        .margin_end(8)
-        .build();
-    Frame::builder()
-        .css_classes(vec!["app-frame"])
-        .child(&icon_image)
-        .build()
+    // Add the class after mapping so that the CSS transition runs
+    let window = window.clone();
+    glib::idle_add_local_once(move || window.add_css_class(SHOWN_CLASS));
+}
+
+fn close_window(window: ApplicationWindow, fade_duration: u64) {
+    if !window.is_visible() {
+        window.close();
+        return
+    }
+    window.remove_css_class(SHOWN_CLASS);
+    glib::timeout_add_local_once(Duration::from_millis(fade_duration), move || window.close());
+}
+
+fn create_tiles(source: &Source, view: View, sender: &UnboundedSender<GuiOp>, tiles: &mut Tiles) -> Vec<Tile> {
+    update_tiles(source, view, HashMap::new(), sender, tiles)
+}
+
+// Tiles whose key and shape are unchanged keep their widgets; only the others are built
+fn update_tiles(source: &Source, view: View, mut old: HashMap<String, Tile>, sender: &UnboundedSender<GuiOp>, tiles: &mut Tiles) -> Vec<Tile> {
+    tile_entries(source, view)
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let kept = tile_key(source, view, i)
+                .and_then(|key| old.remove(&key))
+                .and_then(|mut tile| tiles.update(&mut tile, entry).then_some(tile));
+            let tile = kept.unwrap_or_else(|| {
+                let tile = tiles.build(entry);
+                attach_tile_controllers(&tile.frame, &tile.index, sender);
+                attach_tab_controllers(&tile.tabs, &tile.index, sender);
+                tile
+            });
+            tile.index.set(i);
+            tile
+        })
+        .collect()
+}
+
+fn tile_entries(source: &Source, view: View) -> Vec<TileEntry<'_>> {
+    let Source { apps, entries, .. } = source;
+    match view {
+        View::Apps => apps.iter()
+            .map(|(app_id, windows)| TileEntry::App(app_id, windows))
+            .collect(),
+        View::Windows(app) => apps.get(app)
+            .map(|(app_id, windows)| window_entries(windows)
+                .into_iter()
+                .map(|entry| match entry.as_slice() {
+                    &[single] => TileEntry::Window(app_id, &windows[single]),
+                    _ => {
+                        let members = entry.iter().map(|&it| &windows[it]).collect();
+                        let active = entry.iter().enumerate().min_by_key(|&(_, it)| it).map_or(0, |(i, _)| i);
+                        TileEntry::Group(app_id, members, active)
+                    }
+                })
+                .collect())
+            .unwrap_or_default(),
+        View::Launcher => apps.iter()
+            .map(|(app_id, windows)| (app_id.as_str(), windows.as_slice()))
+            .chain(entries.iter().map(|it| (it.app_id(), [].as_slice())))
+            .map(|(app_id, windows)| TileEntry::App(app_id, windows))
+            .collect(),
+        View::Dmenu => source.items
+            .iter()
+            .map(|it| TileEntry::Item(&it.text, it.icon.as_deref()))
+            .collect(),
+        View::Provider => source.provided
+            .iter()
+            .map(|(_, it)| TileEntry::Item(it.label(), it.icon()))
+            .collect()
+    }
+}
+
+fn tile_key(source: &Source, view: View, tile: usize) -> Option<String> {
+    let apps = &source.apps;
+    match view {
+        View::Apps => apps.get(tile).map(|(app_id, _)| app_id.clone()),
+        // Unlike the active member, the set of members stays the same when switching tabs
+        View::Windows(app) => apps.get(app)
+            .and_then(|(_, windows)| window_entries(windows).into_iter().nth(tile)
+                .and_then(|entry| entry.iter().map(|&it| windows[it].addr().to_string()).min())),
+        View::Launcher => apps.get(tile)
+            .map(|(app_id, _)| app_id.clone())
+            .or_else(|| source.entries.get(tile - apps.len()).map(|it| it.app_id().to_string())),
+        View::Dmenu | View::Provider => None
+    }
+}
+
+fn source_len(source: &Source, view: View) -> usize {
+    match view {
+        View::Apps => source.apps.len(),
+        View::Windows(app) => source.apps.get(app).map_or(0, |(_, windows)| window_entries(windows).len()),
+        View::Launcher => source.apps.len() + source.entries.len(),
+        View::Dmenu => source.items.len(),
+        View::Provider => source.provided.len()
+    }
+}
+
+// Members of a Hyprland group are listed as one entry, in the order of the tabs
+fn window_entries(windows: &[Window]) -> Vec<Vec<usize>> {
+    let mut listed = vec![false; windows.len()];
+    let mut entries = Vec::new();
+    for (i, window) in windows.iter().enumerate() {
+        if listed[i] {
+            continue
+        }
+        let mut entry: Vec<_> = window.group()
+            .iter()
+            .filter_map(|addr| windows.iter().position(|it| it.addr() == addr))
+            .filter(|&it| !listed[it])
+            .collect();
+        if !entry.contains(&i) {
+            entry.insert(0, i);
+        }
+        entry.iter().for_each(|&it| listed[it] = true);
+        entries.push(entry);
+    }
+    entries
+}
+
+fn snapshot_apps(app_manager: &Mutex<AppManager>, config: &Config) -> Applications {
+    let mut apps = app_manager.lock().unwrap().get_apps().clone();
+    if config.urgent_first {
+        apps.sort_by_key(|(_, windows)| !windows.iter().any(|it| it.state().urgent));
+    }
+    apps
+}
+
+fn same_app_ids(old: &Applications, new: &Applications) -> bool {
+    old.len() == new.len() && old.iter().map(|(it, _)| it).collect::<HashSet<_>>() == new.iter().map(|(it, _)| it).collect()
+}
+
+fn filter_order(tiles: &[Tile], query: &str) -> Vec<usize> {
+    let mut scores: Vec<_> = tiles.iter()
+        .enumerate()
+        .filter_map(|(i, tile)| tile.filter(query).map(|score| (i, score)))
+        .collect();
+    scores.sort_by_key(|&(_, score)| Reverse(score));
+    scores.into_iter().map(|(i, _)| i).collect()
+}
+
+fn launcher_entries(app_info: &AppInfoCache, apps: &Applications, history: &LaunchHistory) -> Vec<DesktopEntry> {
+    let running: HashSet<_> = apps.iter()
+        .filter_map(|(app_id, _)| app_info.find_entry(app_id))
+        .map(|it| it.app_id().to_string())
+        .collect();
+    let mut entries: Vec<_> = app_info.launchable()
+        .into_iter()
+        .filter(|it| !running.contains(it.app_id()))
+        .collect();
+    entries.sort_by_cached_key(|it| (Reverse(history.count(it.app_id())), it.name().to_lowercase()));
+    entries
+}
+
+fn show_tiles(widgets: &Widgets, tiles: &[Tile], order: &[usize]) {
+    let Widgets { scrolled, flow_box, caption, .. } = widgets;
+    while let Some(child) = flow_box.first_child() {
+        // Detach the frame so that it can be inserted again in another position
+        if let Some(child) = child.downcast_ref::<FlowBoxChild>() {
+            child.set_child(None::<&Widget>);
+        }
+        flow_box.remove(&child);
+    }
+    for tile in tiles {
+        tile.frame.remove_css_class(CURRENT_ITEM_CLASS);
+    }
+
+    for &i in order {
+        flow_box.insert(&tiles[i].frame, -1);
+    }
+    scrolled.vadjustment().set_value(0.0);
+    caption.set_label("");
+    if let Some(first) = order.first().map(|&it| &tiles[it]) {
+        first.frame.add_css_class(CURRENT_ITEM_CLASS);
+        caption.set_label(&first.caption);
+    }
+}
+
+// Unlike `show_tiles`, leaves the children which are already in place untouched
+fn sync_tiles(widgets: &Widgets, tiles: &[Tile], order: &[usize]) {
+    let flow_box = &widgets.flow_box;
+    let frames: Vec<&Widget> = order.iter().map(|&it| tiles[it].frame.upcast_ref()).collect();
+    let mut next = flow_box.first_child();
+    while let Some(child) = next {
+        next = child.next_sibling();
+        let Some(child) = child.downcast_ref::<FlowBoxChild>() else { continue };
+        if child.child().is_some_and(|it| frames.contains(&&it)) {
+            continue
+        }
+        child.set_child(None::<&Widget>);
+        flow_box.remove(child);
+    }
+    for (i, &frame) in frames.iter().enumerate() {
+        let position = i as i32;
+        if flow_box.child_at_index(position).and_then(|it| it.child()).as_ref() == Some(frame) {
+            continue
+        }
+        // Detach the frame so that it can be inserted again in another position
+        if let Some(child) = frame.parent().and_then(|it| it.downcast::<FlowBoxChild>().ok()) {
+            child.set_child(None::<&Widget>);
+            flow_box.remove(&child);
+        }
+        flow_box.insert(frame, position);
+    }
+    for tile in tiles {
+        tile.frame.remove_css_class(CURRENT_ITEM_CLASS);
+    }
+}
+
+fn highlight(open: &Open, to: usize) {
+    if let Some(tile) = open.order.get(open.cursor).map(|&it| &open.tiles[it]) {
+        tile.frame.remove_css_class(CURRENT_ITEM_CLASS);
+    }
+    if let Some(tile) = open.order.get(to).map(|&it| &open.tiles[it]) {
+        tile.frame.add_css_class(CURRENT_ITEM_CLASS);
+        open.widgets.caption.set_label(&tile.caption);
+        scroll_to(&open.widgets.scrolled, tile);
+    }
+}
+
+fn scroll_to(scrolled: &ScrolledWindow, tile: &Tile) {
+    let Some(child) = tile.frame.parent() else { return };
+    let allocation = child.allocation();
+    scrolled.vadjustment().clamp_page(allocation.y() as f64, (allocation.y() + allocation.height()) as f64);
+}
+
+fn watch_icon_theme(app_info: &Arc<Mutex<AppInfoCache>>) {
+    let Some(settings) = Settings::default() else { return };
+    app_info.lock().unwrap().set_icon_theme(settings.gtk_icon_theme_name().map(Into::into));
+
+    let app_info = app_info.clone();
+    settings.connect_gtk_icon_theme_name_notify(move |it| {
+        app_info.lock().unwrap().set_icon_theme(it.gtk_icon_theme_name().map(Into::into));
+    });
 }
 
 fn create_application() -> Application {
@@ -204,9 +1013,11 @@ fn create_application() -> Application {
         .build()
 }
 
-fn load_css() -> Result<()> {
+fn load_css(fade_duration: u64) -> Result<()> {
     let css_provider = CssProvider::new();
     css_provider.load_from_data(DEFAULT_CSS);
+    let fade_provider = CssProvider::new();
+    fade_provider.load_from_data(&fade_css(fade_duration));
     let display = &gdk::Display::default().context("Failed to connect to a display")?;
 
     style_context_add_provider_for_display(
@@ -214,6 +1025,11 @@ fn load_css() -> Result<()> {
         &css_provider,
         STYLE_PROVIDER_PRIORITY_USER,
     );
+    style_context_add_provider_for_display(
+        display,
+        &fade_provider,
+        STYLE_PROVIDER_PRIORITY_USER + 1,
+    );
 
     Ok(())
 }
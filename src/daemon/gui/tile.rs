@@ -0,0 +1,416 @@
+use std::{cell::Cell, collections::HashMap, path::{Path, PathBuf}, rc::Rc};
+
+use gtk::{gdk::{MemoryFormat, MemoryTexture, Texture}, gdk_pixbuf::Pixbuf, glib, pango::EllipsizeMode, prelude::*, Align, Frame, Image, Label, Orientation, Overlay, Picture, Widget};
+use hyprland::shared::Address;
+use log::debug;
+
+use crate::daemon::{icon::lookup_icon, app_info::AppInfoCache, app_manager::{Window, WindowState}, thumbnail::{Thumbnail, Thumbnails}};
+
+use super::{filter::{fuzzy_match, highlight_markup}, layout::LayoutParams};
+
+const LETTER_AVATAR_CLASS: &str = "letter-avatar";
+const APP_BADGE_CLASS: &str = "app-badge";
+const TILE_LABEL_CLASS: &str = "tile-label";
+const TILE_LABEL_WIDTH: i32 = 14;
+const LIST_LABEL_WIDTH: i32 = 48;
+const WINDOW_COUNT_CLASS: &str = "window-count";
+const INDICATORS_CLASS: &str = "state-indicators";
+const URGENT_CLASS: &str = "urgent";
+const GROUP_TABS_CLASS: &str = "group-tabs";
+const GROUP_TAB_CLASS: &str = "group-tab";
+pub(super) const ACTIVE_TAB_CLASS: &str = "active-tab";
+const GROUP_TAB_WIDTH: i32 = 10;
+
+pub(super) struct Tile {
+    pub(super) frame: Frame,
+    pub(super) caption: String,
+    pub(super) tabs: Vec<Label>,
+    // Shared with the input controllers, since a kept tile may move to another position
+    pub(super) index: Rc<Cell<usize>>,
+    label: Label,
+    text: String,
+    keywords: Vec<String>,
+    shape: Shape
+}
+
+// What a tile shows, borrowed from the source it is built from
+pub(super) enum TileEntry<'a> {
+    App(&'a str, &'a [Window]),
+    Window(&'a str, &'a Window),
+    // A Hyprland group with the index of its active member
+    Group(&'a str, Vec<&'a Window>, usize),
+    Item(&'a str, Option<&'a str>)
+}
+
+// Everything the widgets of a tile depend on, except for the texts which can be replaced in place
+#[derive(PartialEq, Eq)]
+struct Shape {
+    count: usize,
+    state: WindowState,
+    windows: Vec<Address>,
+    active: usize
+}
+
+struct Texts {
+    label: String,
+    tooltip: String,
+    keywords: Vec<String>,
+    tabs: Vec<String>
+}
+
+pub(super) type TextureCache = HashMap<(PathBuf, i32), Option<Texture>>;
+
+pub(super) struct Tiles<'a> {
+    pub(super) app_info: &'a mut AppInfoCache,
+    pub(super) thumbnails: &'a Thumbnails,
+    pub(super) textures: &'a mut TextureCache,
+    pub(super) layout: &'a LayoutParams
+}
+
+impl Tiles<'_> {
+    pub(super) fn build(&mut self, entry: &TileEntry) -> Tile {
+        let texts = self.texts(entry);
+        let shape = Shape::of(entry);
+        let (frame, label, tabs) = match *entry {
+            TileEntry::App(app_id, _) => {
+                let icon = decorate(&self.app_icon(app_id, self.layout.icon_size), shape.count, shape.state);
+                let (frame, label) = tile_frame(&icon, &texts, shape.state, self.layout.orientation, &["app-frame"]);
+                (frame, label, Vec::new())
+            }
+            TileEntry::Window(app_id, window) => {
+                let child = decorate(&self.window_content(app_id, window), 1, shape.state);
+                let (frame, label) = tile_frame(&child, &texts, shape.state, self.layout.orientation, &["app-frame", "window-frame"]);
+                (frame, label, Vec::new())
+            }
+            TileEntry::Group(app_id, ref members, active) => {
+                let (content, tabs) = self.group_content(app_id, members, active, &texts, shape.state);
+                let (frame, label) = tile_frame(&content, &texts, shape.state, self.layout.orientation, &["app-frame", "window-frame", "group-frame"]);
+                (frame, label, tabs)
+            }
+            TileEntry::Item(text, icon) => {
+                let icon = self.item_icon(text, icon);
+                let (frame, label) = tile_frame(&icon, &texts, shape.state, self.layout.orientation, &["app-frame", "item-frame"]);
+                (frame, label, Vec::new())
+            }
+        };
+        Tile {
+            frame,
+            caption: texts.label.clone(),
+            tabs,
+            index: Rc::new(Cell::new(0)),
+            label,
+            text: texts.label,
+            keywords: texts.keywords,
+            shape
+        }
+    }
+
+    // Replaces the texts of a tile built from an equivalent entry; returns false if it has to be rebuilt
+    pub(super) fn update(&mut self, tile: &mut Tile, entry: &TileEntry) -> bool {
+        if tile.shape != Shape::of(entry) {
+            return false
+        }
+        let texts = self.texts(entry);
+        tile.label.set_label(&texts.label);
+        tile.frame.set_tooltip_text(Some(&texts.tooltip));
+        for (tab, title) in tile.tabs.iter().zip(&texts.tabs) {
+            tab.set_label(title);
+            tab.set_tooltip_text(Some(title));
+        }
+        tile.caption = texts.label.clone();
+        tile.text = texts.label;
+        tile.keywords = texts.keywords;
+        true
+    }
+
+    fn texts(&mut self, entry: &TileEntry) -> Texts {
+        match *entry {
+            TileEntry::App(app_id, windows) => {
+                let name = self.app_info.get(app_id).name().to_string();
+                let keywords = std::iter::once(app_id.to_string())
+                    .chain(windows.iter().flat_map(|it| [it.class().to_string(), it.title().to_string()]))
+                    .collect();
+                Texts {
+                    tooltip: windows.first().map_or(name.as_str(), Window::title).to_string(),
+                    label: name,
+                    keywords,
+                    tabs: Vec::new()
+                }
+            }
+            TileEntry::Window(app_id, window) => Texts {
+                label: window.title().to_string(),
+                tooltip: window.title().to_string(),
+                keywords: vec![window.class().to_string(), self.app_info.get(app_id).name().to_string()],
+                tabs: Vec::new()
+            },
+            TileEntry::Group(app_id, ref members, active) => {
+                let keywords = std::iter::once(self.app_info.get(app_id).name().to_string())
+                    .chain(members.iter().flat_map(|it| [it.class().to_string(), it.title().to_string()]))
+                    .collect();
+                Texts {
+                    label: members[active].title().to_string(),
+                    tooltip: members[active].title().to_string(),
+                    keywords,
+                    tabs: members.iter().map(|it| it.title().to_string()).collect()
+                }
+            }
+            TileEntry::Item(text, _) => Texts {
+                label: text.to_string(),
+                tooltip: text.to_string(),
+                keywords: Vec::new(),
+                tabs: Vec::new()
+            }
+        }
+    }
+
+    // A Hyprland group shows its active member with the titles of all members as tabs below
+    fn group_content(&mut self, app_id: &str, members: &[&Window], active: usize, texts: &Texts, state: WindowState) -> (Widget, Vec<Label>) {
+        let tab_bar = gtk::Box::builder()
+            .orientation(Orientation::Horizontal)
+            .css_classes(vec![GROUP_TABS_CLASS])
+            .homogeneous(true)
+            .build();
+        let tabs: Vec<_> = texts.tabs.iter()
+            .map(|title| {
+                let tab = Label::builder()
+                    .label(title)
+                    .tooltip_text(title)
+                    .css_classes(vec![GROUP_TAB_CLASS])
+                    .ellipsize(EllipsizeMode::End)
+                    .max_width_chars(GROUP_TAB_WIDTH)
+                    .single_line_mode(true)
+                    .build();
+                tab_bar.append(&tab);
+                tab
+            })
+            .collect();
+        tabs[active].add_css_class(ACTIVE_TAB_CLASS);
+        let content = gtk::Box::builder()
+            .orientation(Orientation::Vertical)
+            .build();
+        content.append(&decorate(&self.window_content(app_id, members[active]), members.len(), state));
+        content.append(&tab_bar);
+        (content.upcast(), tabs)
+    }
+
+    fn window_content(&mut self, app_id: &str, window: &Window) -> Widget {
+        let size = self.layout.icon_size;
+        match self.thumbnails.get(window.addr()).map(load_thumbnail) {
+            Some(thumbnail) => {
+                let picture = Picture::builder()
+                    .paintable(&thumbnail)
+                    .can_shrink(true)
+                    .height_request(size)
+                    .width_request(size * 16 / 9)
+                    .margin_top(8)
+                    .margin_bottom(8)
+                    .margin_start(8)
+                    .margin_end(8)
+                    .build();
+                let badge = self.app_icon(app_id, size / 3);
+                badge.set_halign(Align::End);
+                badge.set_valign(Align::End);
+                badge.add_css_class(APP_BADGE_CLASS);
+                let overlay = Overlay::builder()
+                    .child(&picture)
+                    .build();
+                overlay.add_overlay(&badge);
+                overlay.upcast()
+            }
+            None => self.app_icon(app_id, size)
+        }
+    }
+
+    fn item_icon(&mut self, text: &str, icon: Option<&str>) -> Widget {
+        let size = self.layout.icon_size;
+        let pixel_size = size * self.app_info.icon_params().scale as i32;
+        icon.and_then(|it| match Path::new(it) {
+                path if path.is_absolute() => Some(path.to_path_buf()),
+                _ => lookup_icon(it, self.app_info.icon_params())
+            })
+            .and_then(|it| load_texture(&it, pixel_size, self.textures))
+            .map_or_else(|| letter_avatar(text, size), |it| icon_image(&it, size))
+    }
+
+    fn app_icon(&mut self, app_id: &str, size: i32) -> Widget {
+        let pixel_size = size * self.app_info.icon_params().scale as i32;
+        let info = self.app_info.get(app_id);
+        info.icon()
+            .and_then(|it| load_texture(it, pixel_size, self.textures))
+            .map_or_else(|| letter_avatar(info.name(), size), |it| icon_image(&it, size))
+    }
+}
+
+impl Shape {
+    fn of(entry: &TileEntry) -> Self {
+        match *entry {
+            TileEntry::App(_, windows) => Self {
+                count: windows.len(),
+                state: merged_state(windows.iter()),
+                windows: Vec::new(),
+                active: 0
+            },
+            TileEntry::Window(_, window) => Self {
+                count: 1,
+                state: window.state(),
+                windows: vec![window.addr().clone()],
+                active: 0
+            },
+            TileEntry::Group(_, ref members, active) => Self {
+                count: members.len(),
+                state: merged_state(members.iter().copied()),
+                windows: members.iter().map(|it| it.addr().clone()).collect(),
+                active
+            },
+            TileEntry::Item(..) => Self {
+                count: 0,
+                state: WindowState::default(),
+                windows: Vec::new(),
+                active: 0
+            }
+        }
+    }
+}
+
+impl Tile {
+    pub(super) fn filter(&self, query: &str) -> Option<i64> {
+        let label_match = fuzzy_match(query, &self.text);
+        let score = self.keywords.iter()
+            .filter_map(|it| fuzzy_match(query, it))
+            .chain(label_match.clone())
+            .map(|(score, _)| score)
+            .max();
+        let positions = label_match.map(|(_, it)| it).unwrap_or_default();
+        self.label.set_markup(&highlight_markup(&self.text, &positions));
+        score
+    }
+}
+
+fn merged_state<'a>(windows: impl Iterator<Item = &'a Window>) -> WindowState {
+    windows.map(Window::state).fold(WindowState::default(), WindowState::merge)
+}
+
+fn decorate(content: &Widget, window_count: usize, state: WindowState) -> Widget {
+    let overlay = Overlay::builder()
+        .child(content)
+        .build();
+
+    if window_count > 1 {
+        let count = Label::builder()
+            .label(window_count.to_string())
+            .css_classes(vec![WINDOW_COUNT_CLASS])
+            .halign(Align::End)
+            .valign(Align::Start)
+            .build();
+        overlay.add_overlay(&count);
+    }
+
+    let indicators = gtk::Box::builder()
+        .orientation(Orientation::Horizontal)
+        .css_classes(vec![INDICATORS_CLASS])
+        .halign(Align::Start)
+        .valign(Align::End)
+        .build();
+    let icons = [
+        (state.urgent, "urgent", "dialog-warning-symbolic"),
+        (state.fullscreen, "fullscreen", "view-fullscreen-symbolic"),
+        (state.floating, "floating", "window-new-symbolic"),
+        (state.pinned, "pinned", "view-pin-symbolic")
+    ];
+    for (_, class, icon_name) in icons.into_iter().filter(|(enabled, ..)| *enabled) {
+        let icon = Image::builder()
+            .icon_name(icon_name)
+            .css_classes(vec!["indicator", class])
+            .build();
+        indicators.append(&icon);
+    }
+    if state.xwayland {
+        let label = Label::builder()
+            .label("X11")
+            .css_classes(vec!["indicator", "xwayland"])
+            .build();
+        indicators.append(&label);
+    }
+    if indicators.first_child().is_some() {
+        overlay.add_overlay(&indicators);
+    }
+
+    overlay.upcast()
+}
+
+fn tile_frame(content: &Widget, texts: &Texts, state: WindowState, orientation: Orientation, css_classes: &[&str]) -> (Frame, Label) {
+    let label = Label::builder()
+        .label(&texts.label)
+        .css_classes(vec![TILE_LABEL_CLASS])
+        .ellipsize(EllipsizeMode::End)
+        .max_width_chars(TILE_LABEL_WIDTH)
+        .single_line_mode(true)
+        .margin_bottom(8)
+        .build();
+    let container = gtk::Box::builder()
+        .orientation(orientation)
+        .build();
+    if orientation == Orientation::Horizontal {
+        label.set_xalign(0.0);
+        label.set_margin_bottom(0);
+        label.set_margin_end(8);
+        label.set_max_width_chars(LIST_LABEL_WIDTH);
+    }
+    container.append(content);
+    container.append(&label);
+    let frame = Frame::builder()
+        .css_classes(css_classes.to_vec())
+        .tooltip_text(&texts.tooltip)
+        .child(&container)
+        .build();
+    if state.urgent {
+        frame.add_css_class(URGENT_CLASS);
+    }
+    (frame, label)
+}
+
+fn load_texture(path: &Path, pixel_size: i32, textures: &mut TextureCache) -> Option<Texture> {
+    textures.entry((path.to_path_buf(), pixel_size))
+        .or_insert_with(|| Pixbuf::from_file_at_size(path, pixel_size, pixel_size)
+            .inspect_err(|e| debug!("Failed to load the icon {path:?}: {e}"))
+            .ok()
+            .map(|it| Texture::for_pixbuf(&it)))
+        .clone()
+}
+
+fn load_thumbnail(thumbnail: &Thumbnail) -> Texture {
+    let bytes = glib::Bytes::from(thumbnail.pixels.as_slice());
+    MemoryTexture::new(thumbnail.width, thumbnail.height, MemoryFormat::R8g8b8a8, &bytes, thumbnail.width as usize * 4).upcast()
+}
+
+fn icon_image(texture: &Texture, size: i32) -> Widget {
+    Image::builder()
+        .paintable(texture)
+        .pixel_size(size)
+        .height_request(size)
+        .width_request(size)
+        .margin_top(8)
+        .margin_bottom(8)
+        .margin_start(8)
+        .margin_end(8)
+        .build()
+        .upcast()
+}
+
+fn letter_avatar(name: &str, size: i32) -> Widget {
+    let letter = name.chars()
+        .next()
+        .map(|it| it.to_uppercase().to_string())
+        .unwrap_or_default();
+    Label::builder()
+        .label(letter)
+        .css_classes(vec![LETTER_AVATAR_CLASS])
+        .height_request(size)
+        .width_request(size)
+        .margin_top(8)
+        .margin_bottom(8)
+        .margin_start(8)
+        .margin_end(8)
+        .build()
+        .upcast()
+}
@@ -1,12 +1,61 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub(crate) fn lookup_icon(class_name: &str) -> Option<PathBuf> {
-    lookup_icon_inner(class_name)
-        .or_else(|| lookup_icon_inner(&class_name.to_ascii_lowercase()))
+use super::desktop::DesktopEntry;
+
+const FALLBACK_ICON: &str = "application-x-executable";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct IconParams {
+    pub(crate) theme: Option<String>,
+    pub(crate) size: u16,
+    pub(crate) scale: u16
+}
+
+impl IconParams {
+    pub(crate) const fn new(size: u16) -> Self {
+        Self {
+            theme: None,
+            size,
+            scale: 1
+        }
+    }
+}
+
+pub(crate) fn lookup_icon(class_name: &str, params: &IconParams) -> Option<PathBuf> {
+    lookup_icon_inner(class_name, params)
+        .or_else(|| lookup_icon_inner(&class_name.to_ascii_lowercase(), params))
+}
+
+pub(crate) fn lookup_app_icon(class_name: &str, entry: Option<&DesktopEntry>, params: &IconParams) -> Option<PathBuf> {
+    entry.and_then(DesktopEntry::icon)
+        .and_then(|it| lookup_entry_icon(it, params))
+        .or_else(|| lookup_icon(class_name, params))
+}
+
+pub(crate) fn lookup_fallback_icon(params: &IconParams) -> Option<PathBuf> {
+    lookup_icon_inner(FALLBACK_ICON, params)
+}
+
+fn lookup_entry_icon(icon: &str, params: &IconParams) -> Option<PathBuf> {
+    let path = Path::new(icon);
+    if path.is_absolute() {
+        return path.exists().then(|| path.to_path_buf())
+    }
+
+    let name = icon.strip_suffix(".png")
+        .or_else(|| icon.strip_suffix(".svg"))
+        .or_else(|| icon.strip_suffix(".xpm"))
+        .unwrap_or(icon);
+    lookup_icon_inner(name, params)
 }
 
-fn lookup_icon_inner(class_name: &str) -> Option<PathBuf> {
-    freedesktop_icons::lookup(class_name)
-        .with_size(96)
-        .find()
+fn lookup_icon_inner(class_name: &str, params: &IconParams) -> Option<PathBuf> {
+    let builder = freedesktop_icons::lookup(class_name)
+        .with_size(params.size)
+        .with_scale(params.scale)
+        .force_svg();
+    match &params.theme {
+        Some(theme) => builder.with_theme(theme).find(),
+        None => builder.find()
+    }
 }
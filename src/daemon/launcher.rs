@@ -0,0 +1,188 @@
+use std::{collections::HashMap, env, fs, os::unix::process::CommandExt as _, path::PathBuf, process::{Command, Stdio}, thread};
+
+use anyhow::{bail, Context as _, Result};
+use log::debug;
+
+use super::desktop::DesktopEntry;
+
+#[derive(Debug, Default)]
+pub(crate) struct LaunchHistory {
+    counts: HashMap<String, u32>
+}
+
+impl LaunchHistory {
+    pub(crate) fn load() -> Self {
+        let counts = fs::read_to_string(get_history_path())
+            .ok()
+            .and_then(|raw| toml::from_str(&raw)
+                .inspect_err(|e| debug!("Failed to parse the launch history: {e}"))
+                .ok())
+            .unwrap_or_default();
+        Self {
+            counts
+        }
+    }
+
+    pub(crate) fn count(&self, app_id: &str) -> u32 {
+        self.counts.get(app_id).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn record(&mut self, app_id: &str) {
+        *self.counts.entry(app_id.to_string()).or_default() += 1;
+        if let Err(e) = self.save() {
+            debug!("Failed to save the launch history: {e:?}");
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = get_history_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create the state directory")?;
+        }
+        let raw = toml::to_string(&self.counts).context("Failed to encode the launch history")?;
+        fs::write(&path, raw).context("Failed to write the launch history")
+    }
+}
+
+pub(crate) fn launch_entry(entry: &DesktopEntry) -> Result<()> {
+    let Some(exec) = entry.exec() else {
+        bail!("{} has no Exec key", entry.app_id())
+    };
+    let mut args = expand_field_codes(&split_exec(exec)?, entry);
+    if entry.terminal() {
+        let terminal = env::var("TERMINAL").unwrap_or_else(|_| "xterm".to_string());
+        args.splice(0..0, [terminal, "-e".to_string()]);
+    }
+    let Some((program, args)) = args.split_first() else {
+        bail!("Exec of {} is empty", entry.app_id())
+    };
+
+    let mut command = Command::new(program);
+    command.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0);
+    if let Some(dir) = entry.path() {
+        command.current_dir(dir);
+    }
+    let mut child = command.spawn().with_context(|| format!("Failed to launch {program}"))?;
+    debug!("Launched {} as {}", entry.app_id(), child.id());
+    // Reap the child so that it doesn't remain as a zombie after exiting
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// Takes the value with the escapes of the desktop file already resolved,
+// so a backslash is only special inside quotes, in front of one of "`$\
+fn split_exec(exec: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            '\\' if quoted => {
+                let arg = current.get_or_insert_with(String::new);
+                match chars.next() {
+                    Some(c @ ('"' | '`' | '$' | '\\')) => arg.push(c),
+                    Some(c) => {
+                        arg.push('\\');
+                        arg.push(c);
+                    }
+                    None => bail!("Exec has an unterminated quote: {exec}")
+                }
+            }
+            c if c.is_whitespace() && !quoted => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c)
+        }
+    }
+    if quoted {
+        bail!("Exec has an unterminated quote: {exec}")
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+fn expand_field_codes(args: &[String], entry: &DesktopEntry) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "%i" => {
+                if let Some(icon) = entry.icon() {
+                    expanded.push("--icon".to_string());
+                    expanded.push(icon.to_string());
+                }
+                continue;
+            }
+            // sagi never passes files or URLs
+            "%f" | "%F" | "%u" | "%U" => continue,
+            _ => {}
+        }
+
+        let mut arg_expanded = String::new();
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                arg_expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => arg_expanded.push('%'),
+                Some('c') => arg_expanded.push_str(entry.name()),
+                Some('k') => arg_expanded.push_str(&entry.file().to_string_lossy()),
+                _ => {}
+            }
+        }
+        expanded.push(arg_expanded);
+    }
+    expanded
+}
+
+fn get_history_path() -> PathBuf {
+    let mut buf = if let Ok(state_path) = env::var("XDG_STATE_HOME") {
+        PathBuf::from(state_path)
+    } else if let Ok(home) = env::var("HOME") {
+        PathBuf::from(home).join(".local/state")
+    } else {
+        PathBuf::from("/tmp")
+    };
+
+    buf.push("sagi");
+    buf.push("launches.toml");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_exec;
+
+    #[test]
+    fn splits_on_unquoted_whitespace() {
+        assert_eq!(split_exec("firefox  --new-window %u").unwrap(), ["firefox", "--new-window", "%u"]);
+        assert_eq!(split_exec(r#"app "two words" """#).unwrap(), ["app", "two words", ""]);
+    }
+
+    #[test]
+    fn quoted_escapes() {
+        assert_eq!(split_exec(r#"sh -c "echo \$HOME""#).unwrap(), ["sh", "-c", "echo $HOME"]);
+        assert_eq!(split_exec(r#"sh -c "say \"hi\" \`x\` \\""#).unwrap(), ["sh", "-c", r#"say "hi" `x` \"#]);
+        // Anything else keeps its backslash
+        assert_eq!(split_exec(r#"app "a\nb""#).unwrap(), ["app", r"a\nb"]);
+    }
+
+    #[test]
+    fn unquoted_backslash_is_literal() {
+        assert_eq!(split_exec(r"app C:\dir").unwrap(), ["app", r"C:\dir"]);
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        assert!(split_exec(r#"app "open"#).is_err());
+        assert!(split_exec(r#"app "open\"#).is_err());
+    }
+}
@@ -1,15 +1,27 @@
 use std::sync::{Arc, Mutex};
 
+use app_info::AppInfoCache;
 use app_manager::AppManager;
+use config::Config;
 use gui::start_gui;
+use thumbnail::{capture_later, start_capture_worker, Thumbnails};
 use log::{debug, info};
 use tokio::{select, sync::mpsc::unbounded_channel};
 use unix::{exists_socket, remove_socket, ClientListener};
-use wayland::{init_windows, create_hypr_listener};
+use wayland::{active_monitor_scale, init_windows, create_hypr_listener};
 use anyhow::{Context as _, Result};
 
 pub(crate) mod icon;
+mod app_info;
 mod app_manager;
+mod config;
+mod desktop;
+mod focus;
+mod launcher;
+mod provider;
+mod rules;
+mod thumbnail;
+mod toplevel_export;
 mod wayland;
 mod unix;
 mod gui;
@@ -24,16 +36,28 @@ pub(crate) async fn launch_daemon(force: bool) -> Result<()> {
         }
     }
 
-    let mut app_manager = AppManager::new();
+    let config = Config::load()?;
+    let mut app_manager = AppManager::new(&config);
     init_windows(&mut app_manager)?;
+    let mut app_info = AppInfoCache::new(config.icon_size);
+    app_info.set_icon_scale(active_monitor_scale());
+    app_manager.get_apps()
+        .iter()
+        .for_each(|(app_id, _)| { app_info.get(app_id); });
     let app_manager = Arc::new(Mutex::new(app_manager));
-    let mut hypr_listener = create_hypr_listener(&app_manager);
-    let client_listener = ClientListener::new()?;
+    let app_info = Arc::new(Mutex::new(app_info));
+    let thumbnails = Arc::new(Mutex::new(Thumbnails::new(config.snapshot.clone())));
+    start_capture_worker(&thumbnails);
+    for (_, windows) in app_manager.lock().unwrap().get_apps() {
+        windows.iter().for_each(|it| capture_later(&thumbnails, it.addr().clone()));
+    }
     let (tx, rx) = unbounded_channel();
+    let mut hypr_listener = create_hypr_listener(&app_manager, &app_info, &thumbnails, &tx);
+    let client_listener = ClientListener::new()?;
     
     select! {
         res = hypr_listener.start_listener_async() => res.context("Hyprland event listener was closed"),
-        res = client_listener.listen(tx) => res,
-        res = start_gui(app_manager.clone(), rx) => res
+        res = client_listener.listen(app_manager.clone(), config.clone(), tx.clone()) => res,
+        res = start_gui(config.clone(), app_manager.clone(), app_info.clone(), thumbnails.clone(), tx, rx) => res
     }
 }
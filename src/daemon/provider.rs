@@ -0,0 +1,139 @@
+use std::{io::Read as _, os::unix::process::CommandExt as _, process::{Child, Command, Stdio}, sync::mpsc, thread, time::{Duration, Instant}};
+
+use anyhow::{bail, Context as _, Result};
+use log::debug;
+use serde::Deserialize;
+
+const DEFAULT_ACTION: &str = "activate";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProviderConfig {
+    mode: String,
+    command: Vec<String>,
+    #[serde(default = "default_timeout")]
+    timeout: u64
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct ProviderItem {
+    id: String,
+    label: String,
+    icon: Option<String>,
+    action: Option<String>
+}
+
+const fn default_timeout() -> u64 {
+    500
+}
+
+impl ProviderItem {
+    pub(crate) fn label(&self) -> &str {
+        self.label.as_str()
+    }
+
+    pub(crate) fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+}
+
+pub(crate) fn collect_items(providers: &[ProviderConfig], mode: &str) -> Vec<(usize, ProviderItem)> {
+    let handles: Vec<_> = providers.iter()
+        .enumerate()
+        .filter(|(_, it)| it.mode == mode)
+        .map(|(i, provider)| {
+            let provider = provider.clone();
+            (i, thread::spawn(move || run_provider(&provider)))
+        })
+        .collect();
+    if handles.is_empty() {
+        debug!("No provider is declared for the mode {mode}");
+    }
+
+    handles.into_iter()
+        .flat_map(|(i, handle)| {
+            let items = match handle.join() {
+                Ok(Ok(items)) => items,
+                Ok(Err(e)) => {
+                    debug!("Provider {i} of {mode} failed: {e:?}");
+                    Vec::new()
+                }
+                Err(_) => Vec::new()
+            };
+            items.into_iter().map(move |it| (i, it))
+        })
+        .collect()
+}
+
+pub(crate) fn invoke_action(providers: &[ProviderConfig], provider: usize, item: &ProviderItem) -> Result<()> {
+    let Some((program, args)) = providers.get(provider).and_then(|it| it.command.split_first()) else {
+        bail!("Provider {provider} has no command")
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .arg(item.action.as_deref().unwrap_or(DEFAULT_ACTION))
+        .arg(&item.id)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+fn run_provider(provider: &ProviderConfig) -> Result<Vec<ProviderItem>> {
+    let Some((program, args)) = provider.command.split_first() else {
+        bail!("The command is empty")
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        // Its own group, so that background children keeping the stdout open can be killed with it
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+
+    // Read in another thread so that a full pipe doesn't block the provider
+    let mut stdout = child.stdout.take().context("Failed to take the stdout")?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = sender.send(stdout.read_to_end(&mut buf).map(|_| buf));
+    });
+
+    let timeout = Duration::from_millis(provider.timeout);
+    let deadline = Instant::now() + timeout;
+    let output = match receiver.recv_timeout(timeout) {
+        Ok(output) => output.with_context(|| format!("Failed to read the output of {program}"))?,
+        Err(_) => {
+            kill_group(&mut child);
+            bail!("{program} timed out")
+        }
+    };
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status
+        }
+        if Instant::now() >= deadline {
+            kill_group(&mut child);
+            bail!("{program} timed out")
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if !status.success() {
+        bail!("{program} exited with {status}")
+    }
+
+    serde_json::from_slice(&output).context("Failed to parse the items")
+}
+
+fn kill_group(child: &mut Child) {
+    // The child has not been reaped yet, so its pid still names the group
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.wait();
+}
@@ -0,0 +1,243 @@
+use regex::Regex;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use super::app_manager::Window;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct IgnoreRule {
+    name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_opt_regex")]
+    class: Option<Regex>,
+    #[serde(default, deserialize_with = "deserialize_opt_regex")]
+    title: Option<Regex>,
+    #[serde(default, deserialize_with = "deserialize_glob")]
+    workspace: Option<Regex>,
+    floating: Option<bool>,
+    xwayland: Option<bool>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) enum GroupRule {
+    Rewrite {
+        #[serde(deserialize_with = "deserialize_regex")]
+        class: Regex,
+        replace: String
+    },
+    Merge {
+        merge: Vec<String>,
+        into: String
+    },
+    Split {
+        #[serde(deserialize_with = "deserialize_regex")]
+        class: Regex,
+        #[serde(deserialize_with = "deserialize_regex")]
+        title: Regex,
+        app_id: String
+    }
+}
+
+impl IgnoreRule {
+    pub(crate) fn matches(&self, window: &Window) -> bool {
+        self.class.as_ref().is_none_or(|it| it.is_match(window.class()))
+            && self.title.as_ref().is_none_or(|it| it.is_match(window.title()))
+            && self.workspace.as_ref().is_none_or(|it| it.is_match(window.workspace()))
+            && self.floating.is_none_or(|it| it == window.floating())
+            && self.xwayland.is_none_or(|it| it == window.xwayland())
+    }
+
+    pub(crate) fn describe(&self, index: usize) -> String {
+        self.name.clone().unwrap_or_else(|| format!("ignore[{index}]"))
+    }
+
+    const fn has_matcher(&self) -> bool {
+        self.class.is_some() || self.title.is_some() || self.workspace.is_some() || self.floating.is_some() || self.xwayland.is_some()
+    }
+}
+
+impl GroupRule {
+    pub(crate) fn apply(&self, class: &str, title: &str) -> Option<String> {
+        match self {
+            GroupRule::Rewrite { class: pattern, replace } =>
+                pattern.is_match(class).then(|| pattern.replace(class, replace.as_str()).into_owned()),
+            GroupRule::Merge { merge, into } =>
+                merge.iter().any(|it| it == class).then(|| into.clone()),
+            GroupRule::Split { class: class_pattern, title: title_pattern, app_id } =>
+                (class_pattern.is_match(class) && title_pattern.is_match(title)).then(|| app_id.clone())
+        }
+    }
+}
+
+pub(crate) fn find_ignore_rule(rules: &[IgnoreRule], window: &Window) -> Option<usize> {
+    rules.iter().position(|it| it.matches(window))
+}
+
+pub(crate) fn resolve_app_id(rules: &[GroupRule], class: &str, title: &str) -> String {
+    rules.iter()
+        .find_map(|it| it.apply(class, title))
+        .unwrap_or_else(|| class.to_string())
+}
+
+// A rule without any matcher would hide every window
+pub(crate) fn deserialize_ignore_rules<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<IgnoreRule>, D::Error> {
+    let rules = Vec::<IgnoreRule>::deserialize(deserializer)?;
+    if let Some((i, rule)) = rules.iter().enumerate().find(|(_, it)| !it.has_matcher()) {
+        return Err(D::Error::custom(format!("{} sets none of class, title, workspace, floating or xwayland", rule.describe(i))))
+    }
+    Ok(rules)
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    compile_anchored(&pattern).map_err(D::Error::custom)
+}
+
+fn deserialize_opt_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let Some(pattern) = Option::<String>::deserialize(deserializer)? else { return Ok(None) };
+    compile_anchored(&pattern).map(Some).map_err(D::Error::custom)
+}
+
+fn deserialize_glob<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let Some(glob) = Option::<String>::deserialize(deserializer)? else { return Ok(None) };
+    let pattern = glob.split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+    compile_anchored(&pattern).map(Some).map_err(D::Error::custom)
+}
+
+fn compile_anchored(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{pattern})$"))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyprland::shared::Address;
+    use serde::Deserialize;
+
+    use crate::daemon::app_manager::{Window, WindowState};
+
+    use super::{deserialize_ignore_rules, find_ignore_rule, resolve_app_id, GroupRule, IgnoreRule};
+
+    #[derive(Deserialize)]
+    struct Rules {
+        #[serde(default, deserialize_with = "deserialize_ignore_rules")]
+        ignore: Vec<IgnoreRule>,
+        #[serde(default)]
+        group: Vec<GroupRule>
+    }
+
+    fn rules(toml: &str) -> Vec<GroupRule> {
+        toml::from_str::<Rules>(toml).unwrap().group
+    }
+
+    fn ignore_rules(toml: &str) -> Result<Vec<IgnoreRule>, toml::de::Error> {
+        toml::from_str::<Rules>(toml).map(|it| it.ignore)
+    }
+
+    fn window(class: &str, workspace: &str) -> Window {
+        Window::new(Address::new("0x1"), class.to_string(), String::new(), workspace.to_string(), WindowState::default())
+    }
+
+    #[test]
+    fn rewrite_replaces_capture_groups() {
+        let rules = rules(r#"
+            [[group]]
+            type = "rewrite"
+            class = "org\\.gnome\\.(\\w+)"
+            replace = "gnome-$1"
+        "#);
+        assert_eq!(resolve_app_id(&rules, "org.gnome.Nautilus", ""), "gnome-Nautilus");
+    }
+
+    #[test]
+    fn merge_collapses_classes() {
+        let rules = rules(r#"
+            [[group]]
+            type = "merge"
+            merge = ["jetbrains-idea", "jetbrains-studio"]
+            into = "jetbrains"
+        "#);
+        assert_eq!(resolve_app_id(&rules, "jetbrains-idea", ""), "jetbrains");
+        assert_eq!(resolve_app_id(&rules, "jetbrains-studio", ""), "jetbrains");
+        assert_eq!(resolve_app_id(&rules, "jetbrains-clion", ""), "jetbrains-clion");
+    }
+
+    #[test]
+    fn split_matches_title() {
+        let rules = rules(r#"
+            [[group]]
+            type = "split"
+            class = "firefox"
+            title = ".*Private Browsing.*"
+            app_id = "firefox-private"
+        "#);
+        assert_eq!(resolve_app_id(&rules, "firefox", "Home — Private Browsing"), "firefox-private");
+        assert_eq!(resolve_app_id(&rules, "firefox", "Home"), "firefox");
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = rules(r#"
+            [[group]]
+            type = "merge"
+            merge = ["kitty"]
+            into = "terminal"
+
+            [[group]]
+            type = "rewrite"
+            class = "kitty"
+            replace = "kitty-term"
+        "#);
+        assert_eq!(resolve_app_id(&rules, "kitty", ""), "terminal");
+    }
+
+    #[test]
+    fn falls_through_to_class() {
+        let rules = rules(r#"
+            [[group]]
+            type = "merge"
+            merge = ["kitty"]
+            into = "terminal"
+        "#);
+        assert_eq!(resolve_app_id(&rules, "foot", ""), "foot");
+        assert_eq!(resolve_app_id(&[], "foot", ""), "foot");
+    }
+
+    #[test]
+    fn patterns_are_anchored() {
+        let rules = rules(r#"
+            [[group]]
+            type = "rewrite"
+            class = "firefox"
+            replace = "browser"
+        "#);
+        assert_eq!(resolve_app_id(&rules, "firefox", ""), "browser");
+        assert_eq!(resolve_app_id(&rules, "firefox-developer-edition", ""), "firefox-developer-edition");
+    }
+
+    #[test]
+    fn ignore_rule_needs_a_matcher() {
+        assert!(ignore_rules(r#"
+            [[ignore]]
+            name = "pip"
+        "#).is_err());
+        assert!(ignore_rules(r#"
+            [[ignore]]
+            name = "pip"
+            floating = true
+        "#).is_ok());
+    }
+
+    #[test]
+    fn workspace_glob_matches_special_workspaces() {
+        let rules = ignore_rules(r#"
+            [[ignore]]
+            workspace = "special:*"
+        "#).unwrap();
+        assert_eq!(find_ignore_rule(&rules, &window("kitty", "special:scratchpad")), Some(0));
+        assert_eq!(find_ignore_rule(&rules, &window("kitty", "special")), None);
+        assert_eq!(find_ignore_rule(&rules, &window("kitty", "1")), None);
+    }
+}
@@ -0,0 +1,199 @@
+use std::{collections::HashMap, sync::{mpsc::{self, Sender}, Arc, Mutex}, thread, time::Duration};
+
+use anyhow::{bail, Result};
+use hyprland::shared::Address;
+use log::{debug, info};
+
+use super::{config::SnapshotConfig, toplevel_export::{Frame, ToplevelExport}};
+
+// wl_shm formats are little-endian, so the 8888 ones are laid out as BGRA or RGBA in memory
+const FORMAT_ARGB8888: u32 = 0;
+const FORMAT_XRGB8888: u32 = 1;
+const FORMAT_ABGR8888: u32 = 0x3432_4241;
+const FORMAT_XBGR8888: u32 = 0x3432_4258;
+
+#[derive(Debug)]
+pub(crate) struct Thumbnails {
+    config: SnapshotConfig,
+    available: bool,
+    queue: Option<Sender<Address>>,
+    images: HashMap<Address, Thumbnail>
+}
+
+// RGBA pixels without padding between the rows
+#[derive(Debug)]
+pub(crate) struct Thumbnail {
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    pub(crate) pixels: Vec<u8>
+}
+
+impl Thumbnails {
+    pub(crate) fn new(config: SnapshotConfig) -> Self {
+        Self {
+            available: config.enabled,
+            config,
+            queue: None,
+            images: HashMap::new()
+        }
+    }
+
+    pub(crate) const fn available(&self) -> bool {
+        self.available
+    }
+
+    pub(crate) fn get(&self, addr: &Address) -> Option<&Thumbnail> {
+        self.images.get(addr)
+    }
+
+    pub(crate) fn remove(&mut self, addr: &Address) {
+        self.images.remove(addr);
+    }
+}
+
+// Windows are exported one by one with `hyprland-toplevel-export-v1`, so neither other windows nor the switcher
+// end up in the image, and windows on hidden workspaces can be captured too.
+// Without the protocol, the tiles keep showing icons.
+pub(super) fn start_capture_worker(thumbnails: &Arc<Mutex<Thumbnails>>) {
+    let (config, receiver) = {
+        let mut guard = thumbnails.lock().unwrap();
+        if !guard.available {
+            return
+        }
+        let (sender, receiver) = mpsc::channel::<Address>();
+        guard.queue = Some(sender);
+        (guard.config.clone(), receiver)
+    };
+
+    let thumbnails = Arc::clone(thumbnails);
+    thread::spawn(move || {
+        let mut export = match ToplevelExport::connect() {
+            Ok(Some(it)) => Some(it),
+            Ok(None) => {
+                info!("The compositor does not support hyprland-toplevel-export-v1, disabling thumbnails");
+                disable(&thumbnails);
+                return
+            }
+            Err(e) => {
+                info!("Failed to connect to the compositor, disabling thumbnails: {e:?}");
+                disable(&thumbnails);
+                return
+            }
+        };
+        let delay = Duration::from_millis(config.delay);
+        while let Ok(addr) = receiver.recv() {
+            // Wait until a burst of focus changes is over, and capture every window involved once
+            let mut pending = vec![addr];
+            while let Ok(next) = receiver.recv_timeout(delay) {
+                if !pending.contains(&next) {
+                    pending.push(next);
+                }
+            }
+            for addr in pending {
+                if export.is_none() {
+                    export = ToplevelExport::connect().ok().flatten();
+                }
+                let Some(connection) = export.as_mut() else { break };
+                match capture(connection, &addr, config.width) {
+                    Ok(Some(image)) => {
+                        thumbnails.lock().unwrap().images.insert(addr, image);
+                    }
+                    Ok(None) => debug!("Window {addr} could not be captured"),
+                    Err(e) => {
+                        debug!("Failed to capture window {addr}: {e:?}");
+                        // The connection is in an unknown state, so start over with a new one
+                        export = None;
+                    }
+                }
+            }
+        }
+    });
+}
+
+pub(super) fn capture_later(thumbnails: &Arc<Mutex<Thumbnails>>, addr: Address) {
+    if let Some(queue) = &thumbnails.lock().unwrap().queue {
+        let _ = queue.send(addr);
+    }
+}
+
+fn disable(thumbnails: &Mutex<Thumbnails>) {
+    let mut thumbnails = thumbnails.lock().unwrap();
+    thumbnails.available = false;
+    thumbnails.queue = None;
+}
+
+fn capture(export: &mut ToplevelExport, addr: &Address, width: u16) -> Result<Option<Thumbnail>> {
+    // Hyprland identifies windows by the lower 32 bits of their address
+    let Ok(handle) = u64::from_str_radix(addr.to_string().trim_start_matches("0x"), 16) else {
+        bail!("Malformed window address")
+    };
+    let Some(frame) = export.capture(handle as u32)? else { return Ok(None) };
+    shrink(&frame, width).map(Some)
+}
+
+// Averages blocks of pixels, so that the thumbnail is at most `max_width` wide
+fn shrink(frame: &Frame, max_width: u16) -> Result<Thumbnail> {
+    let (rgb, alpha) = match frame.format {
+        FORMAT_ARGB8888 => ([2, 1, 0], true),
+        FORMAT_XRGB8888 => ([2, 1, 0], false),
+        FORMAT_ABGR8888 => ([0, 1, 2], true),
+        FORMAT_XBGR8888 => ([0, 1, 2], false),
+        format => bail!("Unsupported buffer format {format:#x}")
+    };
+    let factor = frame.width.div_ceil(max_width.max(1) as usize).max(1);
+    let (width, height) = (frame.width / factor, frame.height / factor);
+    if width == 0 || height == 0 || frame.data.len() < frame.stride * frame.height {
+        bail!("The window has no area")
+    }
+
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0usize; 4];
+            for row in y * factor..(y + 1) * factor {
+                let row = if frame.y_invert { frame.height - 1 - row } else { row };
+                let start = row * frame.stride + x * factor * 4;
+                for pixel in frame.data[start..start + factor * 4].chunks_exact(4) {
+                    for (total, &channel) in sum.iter_mut().zip(&rgb) {
+                        *total += pixel[channel] as usize;
+                    }
+                    sum[3] += if alpha { pixel[3] as usize } else { 255 };
+                }
+            }
+            pixels.extend(sum.map(|it| (it / (factor * factor)) as u8));
+        }
+    }
+    Ok(Thumbnail {
+        width: width as i32,
+        height: height as i32,
+        pixels
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::daemon::toplevel_export::Frame;
+
+    use super::{shrink, FORMAT_ARGB8888, FORMAT_XBGR8888};
+
+    #[test]
+    fn shrink_averages_blocks_into_rgba() {
+        // 4x2 BGRA with padded rows, halved to 2x1
+        let mut data = Vec::new();
+        for _ in 0..2 {
+            data.extend([0, 0, 200, 255, 0, 0, 100, 255, 10, 20, 30, 0, 30, 40, 50, 0, 9, 9, 9, 9]);
+        }
+        let frame = Frame { format: FORMAT_ARGB8888, width: 4, height: 2, stride: 20, y_invert: false, data };
+        let thumbnail = shrink(&frame, 2).unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (2, 1));
+        assert_eq!(thumbnail.pixels, [150, 0, 0, 255, 40, 30, 20, 0]);
+    }
+
+    #[test]
+    fn shrink_flips_inverted_frames() {
+        let data = vec![1, 2, 3, 0, 4, 5, 6, 0];
+        let frame = Frame { format: FORMAT_XBGR8888, width: 1, height: 2, stride: 4, y_invert: true, data };
+        let thumbnail = shrink(&frame, 320).unwrap();
+        assert_eq!(thumbnail.pixels, [4, 5, 6, 255, 1, 2, 3, 255]);
+    }
+}
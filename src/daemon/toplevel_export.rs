@@ -0,0 +1,344 @@
+use std::{env, fs::File, io::{self, Read as _, Write as _}, mem, os::{fd::{AsRawFd as _, FromRawFd as _, OwnedFd, RawFd}, unix::{fs::FileExt as _, net::UnixStream}}, path::PathBuf, ptr, time::Duration};
+
+use anyhow::{bail, Context as _, Result};
+
+// A minimal Wayland client speaking just enough of the wire protocol to use `hyprland-toplevel-export-v1`,
+// which renders a single window into a shared memory buffer, wherever it is and whatever covers it
+
+const MANAGER_INTERFACE: &str = "hyprland_toplevel_export_manager_v1";
+const SHM_INTERFACE: &str = "wl_shm";
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+const DISPLAY: u32 = 1;
+const DISPLAY_SYNC: u16 = 0;
+const DISPLAY_GET_REGISTRY: u16 = 1;
+const DISPLAY_ERROR: u16 = 0;
+const DISPLAY_DELETE_ID: u16 = 1;
+const REGISTRY_BIND: u16 = 0;
+const REGISTRY_GLOBAL: u16 = 0;
+const CALLBACK_DONE: u16 = 0;
+const SHM_CREATE_POOL: u16 = 0;
+const POOL_CREATE_BUFFER: u16 = 0;
+const POOL_DESTROY: u16 = 1;
+const BUFFER_DESTROY: u16 = 0;
+const MANAGER_CAPTURE_TOPLEVEL: u16 = 0;
+const FRAME_COPY: u16 = 0;
+const FRAME_DESTROY: u16 = 1;
+const FRAME_BUFFER: u16 = 0;
+const FRAME_FLAGS: u16 = 2;
+const FRAME_READY: u16 = 3;
+const FRAME_FAILED: u16 = 4;
+const FLAG_Y_INVERT: u32 = 1;
+
+pub(super) struct ToplevelExport {
+    stream: UnixStream,
+    received: Vec<u8>,
+    next_id: u32,
+    // Ids released by the compositor, which are reused so that its object table stays small
+    free_ids: Vec<u32>,
+    shm: u32,
+    manager: u32
+}
+
+// The contents of a window in one of the `wl_shm` formats
+pub(super) struct Frame {
+    pub(super) format: u32,
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) stride: usize,
+    pub(super) y_invert: bool,
+    pub(super) data: Vec<u8>
+}
+
+struct Message {
+    object: u32,
+    opcode: u16,
+    args: Vec<u8>
+}
+
+struct Args<'a>(&'a [u8]);
+
+enum Arg<'a> {
+    Uint(u32),
+    Int(i32),
+    Str(&'a str)
+}
+
+impl ToplevelExport {
+    // Returns None when the compositor does not offer the protocol
+    pub(super) fn connect() -> Result<Option<Self>> {
+        let display = env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
+        let path = if display.starts_with('/') {
+            PathBuf::from(display)
+        } else {
+            PathBuf::from(env::var("XDG_RUNTIME_DIR").context("XDG_RUNTIME_DIR is not set")?).join(display)
+        };
+        let stream = UnixStream::connect(&path).with_context(|| format!("Failed to connect to {path:?}"))?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut export = Self {
+            stream,
+            received: Vec::new(),
+            next_id: DISPLAY + 1,
+            free_ids: Vec::new(),
+            shm: 0,
+            manager: 0
+        };
+
+        let registry = export.new_id();
+        export.send(DISPLAY, DISPLAY_GET_REGISTRY, &[Arg::Uint(registry)], None)?;
+        let mut globals = Vec::new();
+        export.roundtrip(|message| {
+            if message.object == registry && message.opcode == REGISTRY_GLOBAL {
+                let mut args = message.args();
+                globals.push((args.uint()?, args.string()?));
+            }
+            Ok(())
+        })?;
+
+        let find = |interface: &str| globals.iter().find(|(_, it)| it == interface).map(|(name, _)| *name);
+        let (Some(shm), Some(manager)) = (find(SHM_INTERFACE), find(MANAGER_INTERFACE)) else {
+            return Ok(None)
+        };
+        export.shm = export.bind(registry, shm, SHM_INTERFACE)?;
+        export.manager = export.bind(registry, manager, MANAGER_INTERFACE)?;
+        Ok(Some(export))
+    }
+
+    // Returns None when the compositor refuses, e.g. because the window was closed in the meantime
+    pub(super) fn capture(&mut self, handle: u32) -> Result<Option<Frame>> {
+        let frame = self.new_id();
+        self.send(self.manager, MANAGER_CAPTURE_TOPLEVEL, &[Arg::Uint(frame), Arg::Int(0), Arg::Uint(handle)], None)?;
+        let mut buffer = None;
+        let mut failed = false;
+        // The buffer parameters are sent right away, and `buffer_done` is missing from older versions
+        self.roundtrip(|message| {
+            match (message.object == frame, message.opcode) {
+                (true, FRAME_BUFFER) => {
+                    let mut args = message.args();
+                    buffer = Some((args.uint()?, args.uint()? as usize, args.uint()? as usize, args.uint()? as usize));
+                }
+                (true, FRAME_FAILED) => failed = true,
+                _ => {}
+            }
+            Ok(())
+        })?;
+        let Some((format, width, height, stride)) = buffer.filter(|_| !failed) else {
+            self.destroy_frame(frame)?;
+            return Ok(None)
+        };
+
+        let size = stride * height;
+        let file = shm_file(size)?;
+        let pool = self.new_id();
+        self.send(self.shm, SHM_CREATE_POOL, &[Arg::Uint(pool), Arg::Int(size as i32)], Some(file.as_raw_fd()))?;
+        let wl_buffer = self.new_id();
+        self.send(pool, POOL_CREATE_BUFFER, &[Arg::Uint(wl_buffer), Arg::Int(0), Arg::Int(width as i32), Arg::Int(height as i32), Arg::Int(stride as i32), Arg::Uint(format)], None)?;
+        self.send(frame, FRAME_COPY, &[Arg::Uint(wl_buffer), Arg::Int(1)], None)?;
+        let mut flags = 0;
+        let mut ready = false;
+        self.dispatch_until(|message| {
+            if message.object != frame {
+                return Ok(false)
+            }
+            match message.opcode {
+                FRAME_FLAGS => {
+                    flags = message.args().uint()?;
+                    Ok(false)
+                }
+                FRAME_READY => {
+                    ready = true;
+                    Ok(true)
+                }
+                FRAME_FAILED => Ok(true),
+                _ => Ok(false)
+            }
+        })?;
+        self.send(wl_buffer, BUFFER_DESTROY, &[], None)?;
+        self.send(pool, POOL_DESTROY, &[], None)?;
+        // Catch up on the compositor destroying the frame by itself
+        self.roundtrip(|_| Ok(()))?;
+        self.destroy_frame(frame)?;
+        if !ready {
+            return Ok(None)
+        }
+
+        let mut data = vec![0; size];
+        file.read_exact_at(&mut data, 0).context("Failed to read the exported window")?;
+        Ok(Some(Frame {
+            format,
+            width,
+            height,
+            stride,
+            y_invert: flags & FLAG_Y_INVERT != 0,
+            data
+        }))
+    }
+
+    // The compositor may have destroyed a failed frame already, and destroying it twice is a protocol error
+    fn destroy_frame(&mut self, frame: u32) -> Result<()> {
+        if self.free_ids.contains(&frame) {
+            return Ok(())
+        }
+        self.send(frame, FRAME_DESTROY, &[], None)
+    }
+
+    fn bind(&mut self, registry: u32, name: u32, interface: &str) -> Result<u32> {
+        let id = self.new_id();
+        self.send(registry, REGISTRY_BIND, &[Arg::Uint(name), Arg::Str(interface), Arg::Uint(1), Arg::Uint(id)], None)?;
+        Ok(id)
+    }
+
+    fn new_id(&mut self) -> u32 {
+        self.free_ids.pop().unwrap_or_else(|| {
+            let id = self.next_id;
+            self.next_id += 1;
+            id
+        })
+    }
+
+    fn roundtrip(&mut self, mut handler: impl FnMut(&Message) -> Result<()>) -> Result<()> {
+        let callback = self.new_id();
+        self.send(DISPLAY, DISPLAY_SYNC, &[Arg::Uint(callback)], None)?;
+        self.dispatch_until(|message| {
+            if message.object == callback && message.opcode == CALLBACK_DONE {
+                return Ok(true)
+            }
+            handler(message)?;
+            Ok(false)
+        })
+    }
+
+    // Reads events until the handler returns true
+    fn dispatch_until(&mut self, mut handler: impl FnMut(&Message) -> Result<bool>) -> Result<()> {
+        loop {
+            let message = self.read_message()?;
+            match (message.object, message.opcode) {
+                (DISPLAY, DISPLAY_ERROR) => {
+                    let mut args = message.args();
+                    let (object, code, text) = (args.uint()?, args.uint()?, args.string()?);
+                    bail!("The compositor reported an error on object {object} ({code}): {text}")
+                }
+                (DISPLAY, DISPLAY_DELETE_ID) => self.free_ids.push(message.args().uint()?),
+                _ => if handler(&message)? {
+                    return Ok(())
+                }
+            }
+        }
+    }
+
+    fn read_message(&mut self) -> Result<Message> {
+        loop {
+            if self.received.len() >= 8 {
+                let object = u32::from_ne_bytes(self.received[0..4].try_into()?);
+                let word = u32::from_ne_bytes(self.received[4..8].try_into()?);
+                let size = (word >> 16) as usize;
+                if size < 8 {
+                    bail!("Received a malformed message")
+                }
+                if self.received.len() >= size {
+                    let args = self.received[8..size].to_vec();
+                    self.received.drain(..size);
+                    return Ok(Message {
+                        object,
+                        opcode: (word & 0xffff) as u16,
+                        args
+                    })
+                }
+            }
+            let mut chunk = [0; 4096];
+            let read = self.stream.read(&mut chunk).context("Failed to read from the compositor")?;
+            if read == 0 {
+                bail!("The compositor closed the connection")
+            }
+            self.received.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    fn send(&mut self, object: u32, opcode: u16, args: &[Arg], fd: Option<RawFd>) -> Result<()> {
+        let mut message = Vec::new();
+        message.extend_from_slice(&object.to_ne_bytes());
+        message.extend_from_slice(&[0; 4]);
+        for arg in args {
+            match arg {
+                Arg::Uint(value) => message.extend_from_slice(&value.to_ne_bytes()),
+                Arg::Int(value) => message.extend_from_slice(&value.to_ne_bytes()),
+                Arg::Str(value) => {
+                    message.extend_from_slice(&(value.len() as u32 + 1).to_ne_bytes());
+                    message.extend_from_slice(value.as_bytes());
+                    message.push(0);
+                    message.resize(message.len().next_multiple_of(4), 0);
+                }
+            }
+        }
+        let word = ((message.len() as u32) << 16) | opcode as u32;
+        message[4..8].copy_from_slice(&word.to_ne_bytes());
+
+        match fd {
+            Some(fd) => send_with_fd(&self.stream, &message, fd),
+            None => self.stream.write_all(&message).context("Failed to write to the compositor")
+        }
+    }
+}
+
+impl Message {
+    fn args(&self) -> Args<'_> {
+        Args(&self.args)
+    }
+}
+
+impl Args<'_> {
+    fn uint(&mut self) -> Result<u32> {
+        let Some((value, rest)) = self.0.split_first_chunk::<4>() else { bail!("Missing an argument") };
+        self.0 = rest;
+        Ok(u32::from_ne_bytes(*value))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.uint()? as usize;
+        let padded = len.next_multiple_of(4);
+        if len == 0 || self.0.len() < padded {
+            bail!("Missing an argument")
+        }
+        let value = String::from_utf8_lossy(&self.0[..len - 1]).into_owned();
+        self.0 = &self.0[padded..];
+        Ok(value)
+    }
+}
+
+fn shm_file(size: usize) -> Result<File> {
+    let fd = unsafe { libc::memfd_create(c"sagi-toplevel-export".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to create the shared memory")
+    }
+    let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    file.set_len(size as u64).context("Failed to allocate the shared memory")?;
+    Ok(file)
+}
+
+// File descriptors are passed as ancillary data, which std cannot send on stable
+fn send_with_fd(stream: &UnixStream, data: &[u8], fd: RawFd) -> Result<()> {
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut _,
+        iov_len: data.len()
+    };
+    // u64 keeps the buffer aligned for the header
+    let mut control = [0u64; 4];
+    let sent = unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), fd);
+        libc::sendmsg(stream.as_raw_fd(), &msg, libc::MSG_NOSIGNAL)
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to write to the compositor")
+    }
+    let mut stream = stream;
+    stream.write_all(&data[sent as usize..]).context("Failed to write to the compositor")
+}
@@ -1,17 +1,25 @@
-use std::fs;
+use std::{fs, sync::{Arc, Mutex}, time::Duration};
 
 use anyhow::{Context as _, Result};
 use log::debug;
-use tokio::{io::{AsyncReadExt as _, AsyncWriteExt as _}, net::{UnixListener, UnixStream}, sync::mpsc::UnboundedSender};
+use tokio::{io::{AsyncReadExt as _, AsyncWriteExt as _}, net::{UnixListener, UnixStream}, sync::{mpsc::UnboundedSender, oneshot}, time::timeout};
 
-use crate::protocol::{decode_request, encode_response, get_socket_path, Response};
+use crate::protocol::{decode_request, encode_response, get_socket_path, Query, Response, WindowInfo};
 
-use super::gui::GuiOp;
+use super::{app_manager::{AppManager, Window}, config::Config, gui::{FilterEdit, GuiOp}, wayland::activate_window};
+
+// Requests are read in the accept loop, so a client that never closes its end must not stall it
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub(super) struct ClientListener {
     unix_listener: UnixListener
 }
 
+enum Reply {
+    Now(Response),
+    Later(oneshot::Receiver<Option<String>>)
+}
+
 impl ClientListener {
     pub(super) fn new() -> Result<Self> {
         let socket_path = get_socket_path();
@@ -23,19 +31,32 @@ impl ClientListener {
         })
     }
 
-    pub(super) async fn listen(self, mut sender: UnboundedSender<GuiOp>) -> Result<()> {
+    pub(super) async fn listen(self, app_manager: Arc<Mutex<AppManager>>, config: Config, mut sender: UnboundedSender<GuiOp>) -> Result<()> {
         let listener = &self.unix_listener;
 
         loop {
             match listener.accept().await {
                 Ok((mut stream, address)) => {
                     debug!("Connected a client: {address:?}");
-                    match handle(&mut stream, &mut sender).await {
-                        Ok(cont) => {
+                    match handle(&mut stream, &app_manager, &config, &mut sender).await {
+                        Ok(Some(Reply::Now(response))) => {
+                            response_and_shutdown(&mut stream, response).await?;
+                        }
+                        Ok(Some(Reply::Later(receiver))) => {
+                            // Keep accepting other requests while waiting for the user
+                            tokio::spawn(async move {
+                                let response = match receiver.await {
+                                    Ok(Some(selected)) => Response::Selected(selected),
+                                    _ => Response::Refused
+                                };
+                                if let Err(e) = response_and_shutdown(&mut stream, response).await {
+                                    debug!("Failed to send the selection: {e:?}");
+                                }
+                            });
+                        }
+                        Ok(None) => {
                             response_and_shutdown(&mut stream, Response::Accepted).await?;
-                            if !cont {
-                                return Ok(())
-                            }
+                            return Ok(())
                         }
                         Err(e) => {
                             response_and_shutdown(&mut stream, Response::Refused).await?;
@@ -72,20 +93,80 @@ async fn response_and_shutdown(stream: &mut UnixStream, response: Response) -> R
     result
 }
 
-async fn handle(stream: &mut UnixStream, sender: &mut UnboundedSender<GuiOp>) -> Result<bool> {
-    let mut buf = vec![0; 1024];
-    let size = stream.read(&mut buf).await.context("Failed to read the request")?;
-    let req = decode_request(&buf[..size])?;
+async fn handle(stream: &mut UnixStream, app_manager: &Arc<Mutex<AppManager>>, config: &Config, sender: &mut UnboundedSender<GuiOp>) -> Result<Option<Reply>> {
+    let mut buf = Vec::new();
+    let Ok(read) = timeout(REQUEST_TIMEOUT, stream.read_to_end(&mut buf)).await else {
+        debug!("The client did not finish sending the request in time");
+        return Ok(Some(Reply::Now(Response::Refused)))
+    };
+    read.context("Failed to read the request")?;
+    let req = decode_request(&buf)?;
     let op = match req {
         crate::protocol::Request::Launch(launch) => GuiOp::Launch(launch),
         crate::protocol::Request::MoveCursor(d) => GuiOp::MoveCursor(d),
         crate::protocol::Request::ShowWindows => GuiOp::ShowWindows,
-        crate::protocol::Request::SelectCurrent => GuiOp::SelectCurrent,
+        crate::protocol::Request::SelectCurrent(mode) => GuiOp::SelectCurrent(mode),
+        crate::protocol::Request::SelectIndex(index, mode) => GuiOp::SelectIndex(index, mode),
+        crate::protocol::Request::Filter(text) => GuiOp::EditFilter(FilterEdit::Set(text)),
+        crate::protocol::Request::Action(action) => GuiOp::Action(action),
+        crate::protocol::Request::FocusUrgent => {
+            let Some(addr) = app_manager.lock().unwrap().take_urgent() else {
+                debug!("There is no urgent window");
+                return Ok(Some(Reply::Now(Response::Refused)))
+            };
+            let response = match activate_window(addr, config.leave_fullscreen) {
+                Ok(()) => Response::Accepted,
+                Err(e) => {
+                    debug!("Failed to focus the urgent window: {e:?}");
+                    Response::Refused
+                }
+            };
+            // The urgent badge is gone either way
+            sender.send(GuiOp::AppsChanged)?;
+            return Ok(Some(Reply::Now(response)))
+        }
+        crate::protocol::Request::Query(query) => {
+            return Ok(Some(Reply::Now(handle_query(query, &app_manager.lock().unwrap()))))
+        }
+        crate::protocol::Request::Dmenu(items) => {
+            let (reply_sender, receiver) = oneshot::channel();
+            sender.send(GuiOp::Dmenu(items, reply_sender))?;
+            return Ok(Some(Reply::Later(receiver)))
+        }
         crate::protocol::Request::StopDaemon => {
-            return Ok(false)
+            return Ok(None)
         }
     };
     sender.send(op)?;
-    Ok(true)
+    Ok(Some(Reply::Now(Response::Accepted)))
+}
+
+fn handle_query(query: Query, app_manager: &AppManager) -> Response {
+    match query {
+        Query::Windows { show_filtered } => {
+            let mut windows: Vec<_> = app_manager.get_apps()
+                .iter()
+                .flat_map(|(app_id, windows)| windows.iter().map(|it| window_info(app_id.clone(), it, None)))
+                .collect();
+            if show_filtered {
+                windows.extend(app_manager.get_filtered()
+                    .map(|it| window_info(app_manager.app_id(it.window()), it.window(), Some(app_manager.describe_rule(it)))));
+            }
+            Response::Windows(windows)
+        }
+    }
+}
+
+fn window_info(app_id: String, window: &Window, filtered_by: Option<String>) -> WindowInfo {
+    WindowInfo {
+        app_id,
+        class: window.class().to_string(),
+        title: window.title().to_string(),
+        address: window.addr().to_string(),
+        workspace: window.workspace().to_string(),
+        floating: window.floating(),
+        xwayland: window.xwayland(),
+        filtered_by
+    }
 }
 
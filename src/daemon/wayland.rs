@@ -1,53 +1,248 @@
 use std::sync::{Arc, Mutex};
 
-use hyprland::{data::{Client, Clients}, dispatch::{Dispatch, DispatchType, WindowIdentifier}, event_listener::EventListener, shared::{Address, HyprData, HyprError}};
+use hyprland::{data::{Client, Clients, FullscreenMode, Monitor, Monitors, Transforms, Workspace}, dispatch::{Dispatch, DispatchType, FullscreenType, WindowIdentifier, WorkspaceIdentifierWithSpecial}, event_listener::EventListener, shared::{Address, HyprData, WorkspaceId, HyprDataActive, HyprDataActiveOptional}};
 use anyhow::Result;
 use log::debug;
 
-use super::app_manager::AppManager;
+use tokio::sync::mpsc::UnboundedSender;
 
-pub(super) fn create_hypr_listener(app_manager: &Arc<Mutex<AppManager>>) -> EventListener {
+use crate::protocol::WindowAction;
+
+use super::{app_info::AppInfoCache, focus::{focus_window, Hyprctl}, app_manager::{AppManager, Window, WindowState}, gui::GuiOp, thumbnail::{capture_later, Thumbnails}};
+
+pub(super) fn create_hypr_listener(app_manager: &Arc<Mutex<AppManager>>, app_info: &Arc<Mutex<AppInfoCache>>, thumbnails: &Arc<Mutex<Thumbnails>>, sender: &UnboundedSender<GuiOp>) -> EventListener {
     let mut listener = EventListener::new();
 
-    let am = Arc::clone(&app_manager);
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    let ai = Arc::clone(app_info);
+    let th = Arc::clone(thumbnails);
     listener.add_window_opened_handler(move |e| {
         debug!("Window opened: {e:?}");
-        am.lock().unwrap()
-            .add_window(e.window_class, e.window_address, e.window_title);
+        let addr = e.window_address.clone();
+        let window = fetch_client(&e.window_address)
+            .map(window_from_client)
+            .unwrap_or_else(|| Window::new(e.window_address, e.window_class, e.window_title, e.workspace_name, WindowState::default()));
+        let app_id = {
+            let mut am = am.lock().unwrap();
+            let app_id = am.app_id(&window);
+            am.add_window(window);
+            app_id
+        };
+        ai.lock().unwrap()
+            .get(&app_id);
+        capture_later(&th, addr);
+        let _ = tx.send(GuiOp::AppsChanged);
     });
 
-    let am = Arc::clone(&app_manager);
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    let th = Arc::clone(thumbnails);
     listener.add_window_closed_handler(move |addr| {
         debug!("Window closed: {addr:?}");
         am.lock().unwrap()
             .remove_window(&addr);
+        th.lock().unwrap()
+            .remove(&addr);
+        let _ = tx.send(GuiOp::AppsChanged);
     });
 
-    let am = Arc::clone(&app_manager);
+    let am = Arc::clone(app_manager);
+    let th = Arc::clone(thumbnails);
     listener.add_active_window_changed_handler(move |e| {
         if let Some(e) = e {
             debug!("Active window changed: {e:?}");
-            am.lock().unwrap() 
-                .move_to_top(e.class, e.address);
+            am.lock().unwrap()
+                .move_to_top(&e.address);
+            capture_later(&th, e.address);
         }
     });
 
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_window_title_changed_handler(move |e| {
+        debug!("Window title changed: {e:?}");
+        am.lock().unwrap()
+            .update_window(&e.address, |it| it.set_title(e.title));
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_window_moved_handler(move |e| {
+        debug!("Window moved: {e:?}");
+        am.lock().unwrap()
+            .update_window(&e.window_address, |it| it.set_workspace(e.workspace_name.to_string()));
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_float_state_changed_handler(move |e| {
+        debug!("Float state changed: {e:?}");
+        am.lock().unwrap()
+            .update_window(&e.address, |it| it.set_floating(e.floating));
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_urgent_state_changed_handler(move |addr| {
+        debug!("Urgent state changed: {addr:?}");
+        am.lock().unwrap()
+            .mark_urgent(&addr);
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_fullscreen_state_changed_handler(move |fullscreen| {
+        debug!("Fullscreen state changed: {fullscreen}");
+        sync_fullscreen(&am);
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_window_pinned_handler(move |e| {
+        debug!("Window pinned: {e:?}");
+        am.lock().unwrap()
+            .update_window(&e.address, |it| it.set_pinned(e.pinned));
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_group_toggled_handler(move |e| {
+        debug!("Group toggled: {e:?}");
+        sync_groups(&am);
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_window_moved_into_group_handler(move |addr| {
+        debug!("Window moved into a group: {addr:?}");
+        sync_groups(&am);
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
+    let am = Arc::clone(app_manager);
+    let tx = sender.clone();
+    listener.add_window_moved_out_of_group_handler(move |addr| {
+        debug!("Window moved out of a group: {addr:?}");
+        sync_groups(&am);
+        let _ = tx.send(GuiOp::AppsChanged);
+    });
+
     listener
 }
 
-pub(super) fn activate_window(addr: Address) -> Result<(), HyprError> {
-    Dispatch::call(DispatchType::FocusWindow(WindowIdentifier::Address(addr)))
+pub(super) fn activate_window(addr: Address, leave_fullscreen: bool) -> hyprland::Result<()> {
+    let clients: Vec<_> = Clients::get()?.into_iter().collect();
+    let monitors: Vec<_> = Monitors::get()?.into_iter().collect();
+    focus_window(&mut Hyprctl, &addr, &clients, &monitors, leave_fullscreen)
+}
+
+pub(super) fn bring_window(addr: Address, workspace: WorkspaceId, leave_fullscreen: bool) -> hyprland::Result<()> {
+    Dispatch::call(DispatchType::MoveToWorkspaceSilent(WorkspaceIdentifierWithSpecial::Id(workspace), Some(WindowIdentifier::Address(addr.clone()))))?;
+    activate_window(addr, leave_fullscreen)
+}
+
+// Only moving needs a workspace, the one the switcher was opened on if known
+pub(super) fn dispatch_action(action: WindowAction, addr: Address, workspace: Option<WorkspaceId>) -> hyprland::Result<()> {
+    let window = WindowIdentifier::Address(addr.clone());
+    match action {
+        WindowAction::Close | WindowAction::CloseApp => Dispatch::call(DispatchType::CloseWindow(window)),
+        WindowAction::ToggleFullscreen => {
+            // The fullscreen dispatcher only acts on the active window
+            activate_window(addr, false)?;
+            Dispatch::call(DispatchType::ToggleFullscreen(FullscreenType::Real))
+        }
+        WindowAction::ToggleFloating => Dispatch::call(DispatchType::ToggleFloating(Some(window))),
+        WindowAction::Pin => Dispatch::call(DispatchType::TogglePinWindow(window)),
+        WindowAction::MoveHere => {
+            let Some(workspace) = workspace.or_else(active_workspace) else {
+                debug!("Failed to get the active workspace");
+                return Ok(())
+            };
+            Dispatch::call(DispatchType::MoveToWorkspaceSilent(WorkspaceIdentifierWithSpecial::Id(workspace), Some(window)))
+        }
+    }
+}
+
+pub(super) fn active_window() -> Option<Address> {
+    Client::get_active().ok().flatten().map(|it| it.address)
+}
+
+pub(super) fn active_workspace() -> Option<WorkspaceId> {
+    Workspace::get_active().ok().map(|it| it.id)
+}
+
+pub(super) fn active_monitor_scale() -> u16 {
+    Monitor::get_active()
+        .map(|it| it.scale.ceil() as u16)
+        .unwrap_or(1)
+}
+
+pub(super) fn active_monitor_size() -> Option<(i32, i32)> {
+    let monitor = Monitor::get_active().ok()?;
+    let scale = if monitor.scale > 0.0 { monitor.scale } else { 1.0 };
+    let width = (monitor.width as f32 / scale) as i32;
+    let height = (monitor.height as f32 / scale) as i32;
+    match monitor.transform {
+        Transforms::Normal90 | Transforms::Normal270 | Transforms::Flipped90 | Transforms::Flipped270 => Some((height, width)),
+        _ => Some((width, height))
+    }
 }
 
 pub(super) fn init_windows(app_manager: &mut AppManager) -> Result<()> {
     let clients = Clients::get()?;
     debug!("Opened windows:");
     clients.into_iter()
-        .for_each(|Client { class, address, title, .. }| {
-            debug!("    {{ Class: \"{class}\", Title: \"{title}\", Address: {address} }}");
-            app_manager.add_window(class, address, title);
+        .for_each(|client| {
+            debug!("    {{ Class: \"{}\", Title: \"{}\", Address: {} }}", client.class, client.title, client.address);
+            app_manager.add_window(window_from_client(client));
         });
 
     Ok(())
 }
 
+// Group events only name one window, but every member's tab list changes
+fn sync_groups(app_manager: &Mutex<AppManager>) {
+    let Ok(clients) = Clients::get() else { return };
+    let mut app_manager = app_manager.lock().unwrap();
+    for client in clients {
+        let group = client.grouped.into_iter().map(|it| *it).collect();
+        app_manager.update_window(&client.address, |it| it.set_group(group));
+    }
+}
+
+// The event does not name the window, and the active one may already have changed
+fn sync_fullscreen(app_manager: &Mutex<AppManager>) {
+    let Ok(clients) = Clients::get() else { return };
+    let mut app_manager = app_manager.lock().unwrap();
+    for client in clients {
+        let fullscreen = client.fullscreen != FullscreenMode::None;
+        app_manager.update_window(&client.address, |it| it.set_fullscreen(fullscreen));
+    }
+}
+
+fn fetch_client(addr: &Address) -> Option<Client> {
+    Clients::get().ok()?
+        .into_iter()
+        .find(|it| &it.address == addr)
+}
+
+fn window_from_client(Client { class, address, title, workspace, floating, fullscreen, pinned, xwayland, grouped, .. }: Client) -> Window {
+    let state = WindowState {
+        floating,
+        fullscreen: fullscreen != FullscreenMode::None,
+        pinned,
+        urgent: false,
+        xwayland
+    };
+    let mut window = Window::new(address, class, title, workspace.name, state);
+    window.set_group(grouped.into_iter().map(|it| *it).collect());
+    window
+}
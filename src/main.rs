@@ -3,15 +3,15 @@ pub mod cli;
 pub(crate) mod protocol;
 mod client;
 
-use std::{collections::HashMap, convert::identity, future::Future, path::PathBuf};
+use std::{collections::HashMap, convert::identity, future::Future, io::stdin, path::PathBuf};
 
 use cli::{run_cli, CliHandler, CliParams};
-use client::send_request;
-use daemon::{icon::lookup_icon, launch_daemon};
+use client::{query, read_dmenu_items, send_request};
+use daemon::{icon::{lookup_icon, IconParams}, launch_daemon};
 use hyprland::{data::{Client, Clients}, shared::HyprData};
-use anyhow::{Context as _, Result};
+use anyhow::{bail, Context as _, Result};
 use log::debug;
-use protocol::Launch;
+use protocol::{Launch, Response};
 use tokio::runtime::Builder;
 
 #[derive(Clone, Debug)]
@@ -24,7 +24,7 @@ impl Class {
     pub fn new(class_name: &str) -> Self {
         Self {
             clients: vec!(),
-            icon_path: lookup_icon(class_name)
+            icon_path: lookup_icon(class_name, &IconParams::new(96))
         }
     }
 
@@ -50,7 +50,9 @@ impl CliHandler for CliHandlerImpl {
     fn launch(sc: cli::LaunchCommand) -> Self::Output {
         let sub = match sc {
             cli::LaunchCommand::App => Launch::App,
-            cli::LaunchCommand::WindowInApp => Launch::WindowInApp
+            cli::LaunchCommand::WindowInApp => Launch::WindowInApp,
+            cli::LaunchCommand::Launcher => Launch::Launcher,
+            cli::LaunchCommand::Provider { mode } => Launch::Provider(mode)
         };
         send_request(protocol::Request::Launch(sub))
     }
@@ -58,7 +60,13 @@ impl CliHandler for CliHandlerImpl {
     fn move_cursor(direction: cli::Direction) -> Self::Output {
         let direction = match direction {
             cli::Direction::Prev => protocol::Direction::Prev,
-            cli::Direction::Next => protocol::Direction::Next
+            cli::Direction::Next => protocol::Direction::Next,
+            cli::Direction::Up => protocol::Direction::Up,
+            cli::Direction::Down => protocol::Direction::Down,
+            cli::Direction::First => protocol::Direction::First,
+            cli::Direction::Last => protocol::Direction::Last,
+            cli::Direction::PageUp => protocol::Direction::PageUp,
+            cli::Direction::PageDown => protocol::Direction::PageDown
         };
         send_request(protocol::Request::MoveCursor(direction))
     }
@@ -67,8 +75,69 @@ impl CliHandler for CliHandlerImpl {
         send_request(protocol::Request::ShowWindows)
     }
 
-    fn select_current() -> Self::Output {
-        send_request(protocol::Request::SelectCurrent)
+    fn select_current(bring: bool) -> Self::Output {
+        send_request(protocol::Request::SelectCurrent(select_mode(bring)))
+    }
+
+    fn select_index(index: usize, bring: bool) -> Self::Output {
+        send_request(protocol::Request::SelectIndex(index, select_mode(bring)))
+    }
+
+    fn filter(text: String) -> Self::Output {
+        send_request(protocol::Request::Filter(text))
+    }
+
+    fn window_action(action: cli::WindowAction) -> Self::Output {
+        let action = match action {
+            cli::WindowAction::Close => protocol::WindowAction::Close,
+            cli::WindowAction::CloseApp => protocol::WindowAction::CloseApp,
+            cli::WindowAction::ToggleFullscreen => protocol::WindowAction::ToggleFullscreen,
+            cli::WindowAction::ToggleFloating => protocol::WindowAction::ToggleFloating,
+            cli::WindowAction::Pin => protocol::WindowAction::Pin,
+            cli::WindowAction::MoveHere => protocol::WindowAction::MoveHere
+        };
+        send_request(protocol::Request::Action(action))
+    }
+
+    fn focus_urgent() -> Self::Output {
+        send_request(protocol::Request::FocusUrgent)
+    }
+
+    fn query_windows(show_filtered: bool) -> Self::Output {
+        let Response::Windows(windows) = query(protocol::Request::Query(protocol::Query::Windows { show_filtered }))? else {
+            bail!("The request was refused.")
+        };
+        for window in windows {
+            let mut flags = vec![format!("app: {}", window.app_id), format!("workspace: {}", window.workspace)];
+            if window.floating {
+                flags.push("floating".to_string());
+            }
+            if window.xwayland {
+                flags.push("xwayland".to_string());
+            }
+            if let Some(rule) = window.filtered_by {
+                flags.push(format!("filtered by {rule}"));
+            }
+            println!("{}\t{}\t{}\t({})", window.address, window.class, window.title, flags.join(", "));
+        }
+        Ok(())
+    }
+
+    fn dmenu() -> Self::Output {
+        let items = read_dmenu_items(stdin().lock())?;
+        let Response::Selected(selected) = query(protocol::Request::Dmenu(items))? else {
+            bail!("Nothing was selected.")
+        };
+        println!("{selected}");
+        Ok(())
+    }
+}
+
+fn select_mode(bring: bool) -> protocol::SelectMode {
+    if bring {
+        protocol::SelectMode::BringHere
+    } else {
+        protocol::SelectMode::Focus
     }
 }
 
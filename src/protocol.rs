@@ -8,26 +8,82 @@ pub(crate) enum Request {
     Launch(Launch),
     MoveCursor(Direction),
     ShowWindows,
-    SelectCurrent,
+    SelectCurrent(SelectMode),
+    SelectIndex(usize, SelectMode),
+    Filter(String),
+    Action(WindowAction),
+    FocusUrgent,
+    Query(Query),
+    Dmenu(Vec<DmenuItem>),
     StopDaemon
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum Launch {
     App,
-    WindowInApp
+    WindowInApp,
+    Launcher,
+    Provider(String)
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum Direction {
     Prev,
-    Next
+    Next,
+    Up,
+    Down,
+    First,
+    Last,
+    PageUp,
+    PageDown
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub(crate) enum SelectMode {
+    Focus,
+    BringHere
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum WindowAction {
+    Close,
+    CloseApp,
+    ToggleFullscreen,
+    ToggleFloating,
+    Pin,
+    MoveHere
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) enum Query {
+    Windows { show_filtered: bool }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct DmenuItem {
+    pub(crate) text: String,
+    pub(crate) icon: Option<String>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub(crate) enum Response {
     Accepted,
-    Refused
+    Refused,
+    Windows(Vec<WindowInfo>),
+    Selected(String)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct WindowInfo {
+    pub(crate) app_id: String,
+    pub(crate) class: String,
+    pub(crate) title: String,
+    pub(crate) address: String,
+    pub(crate) workspace: String,
+    pub(crate) floating: bool,
+    pub(crate) xwayland: bool,
+    pub(crate) filtered_by: Option<String>
 }
 
 pub(crate) fn encode_request(msg: Request) -> Vec<u8> {